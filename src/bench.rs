@@ -0,0 +1,123 @@
+use std::{
+    io::{BufRead, Write, stdin, stdout},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::{Board, Lan},
+    game_tree::{GameTree, SearchOptions, Table},
+    misc::MEBIBYTES,
+    perft::{parse_depth_and_board, perft},
+};
+
+const TABLE_SIZE: usize = 16 * MEBIBYTES;
+// perft grows too fast to run at the search depth
+const MAX_PERFT_DEPTH: u32 = 5;
+
+// Searches with iterative deepening up to `depth` on a single thread, returns
+// the total nodes searched and the best move
+pub fn bench(board: &Board, depth: u32, options: SearchOptions) -> (u32, Option<Lan>) {
+    let mut game_tree = GameTree::new(board.clone());
+    game_tree.set_options(options);
+    let mut table = Table::new(TABLE_SIZE);
+    let nodes = (1..=depth)
+        .map(|depth| game_tree.calculate(depth, &mut table, 1))
+        .sum();
+    (nodes, game_tree.best_move())
+}
+fn nps(nodes: u64, time: Duration) -> u64 {
+    // precision doesn't matter
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let nps = (nodes as f64 / time.as_secs_f64()) as u64;
+    nps
+}
+// Compares pruned and unpruned search on every position of the input, in the
+// same format as perft. Move generation speed is measured with perft.
+pub fn bench_loop() {
+    let input = stdin().lock();
+    let mut output = stdout().lock();
+    let mut positions = 0;
+    let mut same_best_move = 0;
+    let mut total_pruned = 0_u64;
+    let mut total_unpruned = 0_u64;
+    let mut total_search_time = Duration::ZERO;
+    let mut total_perft = 0_u64;
+    let mut total_perft_time = Duration::ZERO;
+    for line in input.lines() {
+        let line = line.unwrap();
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (depth, board) = match parse_depth_and_board(line) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                eprintln!("Error: {err}");
+                continue;
+            }
+        };
+        let start = Instant::now();
+        let (pruned, pruned_move) = bench(&board, depth, SearchOptions::default());
+        let (unpruned, unpruned_move) = bench(&board, depth, SearchOptions::UNPRUNED);
+        let search_time = start.elapsed();
+        let start = Instant::now();
+        let perft_nodes = perft(&mut board.clone(), Ord::min(depth, MAX_PERFT_DEPTH));
+        let perft_time = start.elapsed();
+        let display = |movement: Option<Lan>| {
+            movement.map_or_else(|| "(none)".to_owned(), |movement| movement.to_string())
+        };
+        writeln!(
+            output,
+            "pruned: {pruned} nodes, best move {}",
+            display(pruned_move)
+        )
+        .unwrap();
+        writeln!(
+            output,
+            "unpruned: {unpruned} nodes, best move {}",
+            display(unpruned_move)
+        )
+        .unwrap();
+        writeln!(
+            output,
+            "search: {} nps",
+            nps(u64::from(pruned) + u64::from(unpruned), search_time)
+        )
+        .unwrap();
+        writeln!(
+            output,
+            "perft: {perft_nodes} nodes, {} nps",
+            nps(perft_nodes, perft_time)
+        )
+        .unwrap();
+        writeln!(output).unwrap();
+        positions += 1;
+        if pruned_move == unpruned_move {
+            same_best_move += 1;
+        }
+        total_pruned += u64::from(pruned);
+        total_unpruned += u64::from(unpruned);
+        total_search_time += search_time;
+        total_perft += perft_nodes;
+        total_perft_time += perft_time;
+    }
+    writeln!(output, "Pruned nodes: {total_pruned}").unwrap();
+    writeln!(output, "Unpruned nodes: {total_unpruned}").unwrap();
+    writeln!(output, "Same best move: {same_best_move}/{positions}").unwrap();
+    writeln!(
+        output,
+        "Search speed: {} nps",
+        nps(total_pruned + total_unpruned, total_search_time)
+    )
+    .unwrap();
+    writeln!(
+        output,
+        "Perft speed: {} nps",
+        nps(total_perft, total_perft_time)
+    )
+    .unwrap();
+}
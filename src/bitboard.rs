@@ -0,0 +1,306 @@
+use std::{
+    iter::FusedIterator,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not},
+};
+
+use crate::{
+    color::Color,
+    coord::{Coord, Vector},
+};
+
+// Bit structure follows `Coord`: bit 0 is a8, bit 7 is h8, bit 63 is h1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+    pub const EMPTY: Self = Bitboard(0);
+    pub const FULL: Self = Bitboard(!0);
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    pub fn contains(self, position: Coord) -> bool {
+        self.0 & (1 << position.index()) != 0
+    }
+    pub fn insert(&mut self, position: Coord) {
+        self.0 |= 1 << position.index();
+    }
+    pub fn remove(&mut self, position: Coord) {
+        self.0 &= !(1 << position.index());
+    }
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+    pub fn first(self) -> Option<Coord> {
+        (!self.is_empty()).then(|| Coord::from_index(self.0.trailing_zeros().try_into().unwrap()))
+    }
+    pub fn last(self) -> Option<Coord> {
+        (!self.is_empty()).then(|| Coord::from_index(self.0.ilog2().try_into().unwrap()))
+    }
+}
+impl From<Coord> for Bitboard {
+    fn from(value: Coord) -> Self {
+        Bitboard(1 << value.index())
+    }
+}
+impl BitAnd for Bitboard {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Bitboard(self.0 & rhs.0)
+    }
+}
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+impl BitOr for Bitboard {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Bitboard(self.0 | rhs.0)
+    }
+}
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+impl BitXor for Bitboard {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Bitboard(self.0 ^ rhs.0)
+    }
+}
+impl BitXorAssign for Bitboard {
+    fn bitxor_assign(&mut self, rhs: Self) {
+        self.0 ^= rhs.0;
+    }
+}
+impl Not for Bitboard {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Bitboard(!self.0)
+    }
+}
+impl IntoIterator for Bitboard {
+    type Item = Coord;
+
+    type IntoIter = BitboardIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        BitboardIter(self.0)
+    }
+}
+#[derive(Debug, Clone)]
+pub struct BitboardIter(u64);
+
+impl Iterator for BitboardIter {
+    type Item = Coord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            None
+        } else {
+            let index = self.0.trailing_zeros();
+            self.0 &= self.0 - 1;
+            Some(Coord::from_index(index.try_into().unwrap()))
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.0.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+impl ExactSizeIterator for BitboardIter {}
+impl FusedIterator for BitboardIter {}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "values are bounded by the board size"
+)]
+const fn offset(index: usize, movement: Vector) -> Option<usize> {
+    let x = (index % 8) as i8 + movement.x;
+    let y = (index / 8) as i8 + movement.y;
+    if x >= 0 && x < 8 && y >= 0 && y < 8 {
+        Some(y as usize * 8 + x as usize)
+    } else {
+        None
+    }
+}
+const fn step_table(moves: &[Vector]) -> [Bitboard; 64] {
+    let mut table = [Bitboard::EMPTY; 64];
+    let mut index = 0;
+    while index < 64 {
+        let mut i = 0;
+        while i < moves.len() {
+            if let Some(destination) = offset(index, moves[i]) {
+                table[index].0 |= 1 << destination;
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    table
+}
+const fn ray_table() -> [[Bitboard; 64]; 8] {
+    let mut table = [[Bitboard::EMPTY; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let mut index = 0;
+        while index < 64 {
+            let mut current = offset(index, Vector::QUEEN_DIRECTIONS[direction]);
+            while let Some(position) = current {
+                table[direction][index].0 |= 1 << position;
+                current = offset(position, Vector::QUEEN_DIRECTIONS[direction]);
+            }
+            index += 1;
+        }
+        direction += 1;
+    }
+    table
+}
+#[allow(clippy::large_stack_arrays, reason = "only evaluated at compile time")]
+const fn between_table() -> [[Bitboard; 64]; 64] {
+    let mut table = [[Bitboard::EMPTY; 64]; 64];
+    let mut index = 0;
+    while index < 64 {
+        let mut direction = 0;
+        while direction < 8 {
+            let mut inside = 0;
+            let mut current = offset(index, Vector::QUEEN_DIRECTIONS[direction]);
+            while let Some(position) = current {
+                table[index][position].0 = inside;
+                inside |= 1 << position;
+                current = offset(position, Vector::QUEEN_DIRECTIONS[direction]);
+            }
+            direction += 1;
+        }
+        index += 1;
+    }
+    table
+}
+static KNIGHT_ATTACKS: [Bitboard; 64] = step_table(&Vector::KNIGHT_MOVES);
+static KING_ATTACKS: [Bitboard; 64] = step_table(&Vector::KING_MOVES);
+static WHITE_PAWN_ATTACKS: [Bitboard; 64] =
+    step_table(&[Vector { x: -1, y: -1 }, Vector { x: 1, y: -1 }]);
+static BLACK_PAWN_ATTACKS: [Bitboard; 64] =
+    step_table(&[Vector { x: -1, y: 1 }, Vector { x: 1, y: 1 }]);
+
+// Indexed by the position of the direction in `Vector::QUEEN_DIRECTIONS`
+static RAYS: [[Bitboard; 64]; 8] = ray_table();
+static BETWEEN: [[Bitboard; 64]; 64] = between_table();
+
+const ROOK_DIRECTIONS: [usize; 4] = [1, 3, 4, 6];
+const BISHOP_DIRECTIONS: [usize; 4] = [0, 2, 5, 7];
+
+fn ray_attacks(position: Coord, occupied: Bitboard, direction: usize) -> Bitboard {
+    let ray = RAYS[direction][position.index() as usize];
+    let movement = Vector::QUEEN_DIRECTIONS[direction];
+    let blockers = ray & occupied;
+
+    // rays toward higher indices are blocked by the lowest bit, the rest by the
+    // highest bit
+    let blocker = if movement.y > 0 || (movement.y == 0 && movement.x > 0) {
+        blockers.first()
+    } else {
+        blockers.last()
+    };
+    match blocker {
+        Some(blocker) => ray ^ RAYS[direction][blocker.index() as usize],
+        None => ray,
+    }
+}
+pub fn knight_attacks(position: Coord) -> Bitboard {
+    KNIGHT_ATTACKS[position.index() as usize]
+}
+pub fn king_attacks(position: Coord) -> Bitboard {
+    KING_ATTACKS[position.index() as usize]
+}
+pub fn pawn_attacks(position: Coord, color: Color) -> Bitboard {
+    match color {
+        Color::White => WHITE_PAWN_ATTACKS[position.index() as usize],
+        Color::Black => BLACK_PAWN_ATTACKS[position.index() as usize],
+    }
+}
+pub fn rook_attacks(position: Coord, occupied: Bitboard) -> Bitboard {
+    ROOK_DIRECTIONS
+        .into_iter()
+        .fold(Bitboard::EMPTY, |attacks, direction| {
+            attacks | ray_attacks(position, occupied, direction)
+        })
+}
+pub fn bishop_attacks(position: Coord, occupied: Bitboard) -> Bitboard {
+    BISHOP_DIRECTIONS
+        .into_iter()
+        .fold(Bitboard::EMPTY, |attacks, direction| {
+            attacks | ray_attacks(position, occupied, direction)
+        })
+}
+pub fn queen_attacks(position: Coord, occupied: Bitboard) -> Bitboard {
+    rook_attacks(position, occupied) | bishop_attacks(position, occupied)
+}
+pub fn ray(position: Coord, direction: Vector) -> Bitboard {
+    let direction = Vector::QUEEN_DIRECTIONS
+        .into_iter()
+        .position(|valid_direction| valid_direction == direction)
+        .expect("direction should be a unit queen direction");
+    RAYS[direction][position.index() as usize]
+}
+// Squares strictly between two positions, empty when they are not aligned
+pub fn between(a: Coord, b: Coord) -> Bitboard {
+    BETWEEN[a.index() as usize][b.index() as usize]
+}
+#[cfg(test)]
+mod test {
+    use crate::{
+        bitboard::{Bitboard, between, bishop_attacks, knight_attacks, rook_attacks},
+        coord::Coord,
+    };
+
+    fn bitboard(positions: &[&str]) -> Bitboard {
+        positions
+            .iter()
+            .fold(Bitboard::EMPTY, |bitboard, position| {
+                bitboard | position.parse::<Coord>().unwrap().into()
+            })
+    }
+    #[test]
+    fn knight_on_corner() {
+        assert_eq!(
+            knight_attacks("a1".parse().unwrap()),
+            bitboard(&["b3", "c2"])
+        );
+    }
+    #[test]
+    fn rook_stops_at_blocker() {
+        let occupied = bitboard(&["e6", "c4", "e2"]);
+        assert_eq!(
+            rook_attacks("e4".parse().unwrap(), occupied),
+            bitboard(&["e5", "e6", "e3", "e2", "d4", "c4", "f4", "g4", "h4"])
+        );
+    }
+    #[test]
+    fn bishop_stops_at_blocker() {
+        let occupied = bitboard(&["b2", "f6"]);
+        assert_eq!(
+            bishop_attacks("d4".parse().unwrap(), occupied),
+            bitboard(&["c3", "b2", "e5", "f6", "c5", "b6", "a7", "e3", "f2", "g1"])
+        );
+    }
+    #[test]
+    fn between_aligned_and_unaligned() {
+        assert_eq!(
+            between("a1".parse().unwrap(), "d4".parse().unwrap()),
+            bitboard(&["b2", "c3"])
+        );
+        assert_eq!(
+            between("a1".parse().unwrap(), "b3".parse().unwrap()),
+            Bitboard::EMPTY
+        );
+    }
+}
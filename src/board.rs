@@ -1,30 +1,38 @@
 use std::{
-    cell::OnceCell,
     cmp::Ordering,
     error::Error,
     fmt::{self, Display, Formatter},
     hash::Hash,
-    iter::{FusedIterator, empty, once},
+    iter::{FusedIterator, once},
     num::NonZero,
-    ops::{Index, IndexMut, Range},
-    rc::Rc,
+    ops::{Index, IndexMut},
     str::FromStr,
 };
 
 use crate::{
+    bitboard::{
+        Bitboard, between, bishop_attacks, king_attacks, knight_attacks, pawn_attacks,
+        queen_attacks, ray, rook_attacks,
+    },
     board_display::IndexableBoard,
     castling_right::CastlingRight,
     color::Color,
-    coord::{Coord, ParseCoordError, RotatedCoord, Vector},
-    end_state::EndState,
+    coord::{Coord, ParseCoordError, Vector},
+    end_state::{EndReason, EndState},
+    fen::Fen,
     heuristics::{Estimated, PawnAdvancement},
-    misc::InvalidByte,
+    history::{History, REPETITION_DRAW},
     piece::{ColoredPieceKind, InvalidFenPiece, PieceKind, STARTING_VALUE},
     simple_board::SimpleBoard,
+    zobrist,
 };
 
 pub const ESTIMATED_TOTAL_MOVES: u8 = 40;
 
+// The 75-move rule is the automatic draw, but the 50-move rule can always be
+// claimed, so positions past it are treated as drawn
+pub const MOVE_RULE_PLIES: u32 = 100;
+
 const INCLUDE_PAWN: bool = false;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -39,490 +47,91 @@ impl Piece {
     pub fn piece(self) -> PieceKind {
         self.piece.piece()
     }
-    fn controlled_squares_step(self, moves: &[Vector]) -> impl Iterator<Item = Coord> {
-        moves
-            .iter()
-            .copied()
-            .filter_map(move |movement| self.position.add_checked(movement))
-    }
-    fn controlled_squares_direction(
-        self,
-        board: &Board,
-        direction: Vector,
-    ) -> impl Iterator<Item = Coord> {
-        let mut resume = true;
-        self.position
-            .line_exclusive(direction)
-            .take_while(move |destination| {
-                if resume {
-                    if board[*destination].is_some() {
-                        resume = false;
-                    }
-                    true
-                } else {
-                    false
-                }
-            })
-    }
-    fn all_controlled_squares_direction(
-        self,
-        board: &Board,
-        directions: &[Vector],
-    ) -> impl Iterator<Item = Coord> {
-        directions
-            .iter()
-            .copied()
-            .flat_map(move |direction| self.controlled_squares_direction(board, direction))
-    }
-    fn step_moves(
-        self,
-        index: PieceIndex,
-        board: &Board,
-        moves: &[Vector],
-    ) -> impl Iterator<Item = SimpleMove> {
-        self.controlled_squares_step(moves)
-            .filter_map(move |destination| {
-                if let Some((capture, piece)) = board.index_and_piece(destination) {
-                    (piece.color() != self.color()).then_some({
-                        SimpleMove {
-                            index,
-                            destination,
-                            capture: Some(capture),
-                        }
-                    })
-                } else {
-                    Some(SimpleMove {
-                        index,
-                        destination,
-                        capture: None,
-                    })
-                }
-            })
-    }
-    fn directional_moves(
-        self,
-        index: PieceIndex,
-        board: &Board,
-        direction: Vector,
-    ) -> impl Iterator<Item = SimpleMove> {
-        let mut resume = true;
-        self.position
-            .line_exclusive(direction)
-            .map_while(move |destination| {
-                if resume {
-                    if let Some((capture, piece)) = board.index_and_piece(destination) {
-                        resume = false;
-                        (piece.color() != self.color()).then_some({
-                            SimpleMove {
-                                index,
-                                destination,
-                                capture: Some(capture),
-                            }
-                        })
-                    } else {
-                        Some(SimpleMove {
-                            index,
-                            destination,
-                            capture: None,
-                        })
-                    }
-                } else {
-                    None
-                }
-            })
-    }
-    fn all_directional_moves(
-        self,
-        index: PieceIndex,
-        board: &Board,
-        directions: &[Vector],
-    ) -> impl Iterator<Item = SimpleMove> {
-        directions
-            .iter()
-            .copied()
-            .flat_map(move |direction| self.directional_moves(index, board, direction))
-    }
-    fn pawn_moves(self, index: PieceIndex, board: &Board) -> impl Iterator<Item = Move> {
-        let forward_jumps = if self.position.y() == Coord::pawn_home_rank(self.color()) {
-            2
-        } else {
-            1
-        };
-        self.position
-            .line_exclusive(Vector::pawn_single_move(self.color()))
-            .take(forward_jumps)
-            .take_while(|position| board[*position].is_none())
-            .map(move |destination| Move {
-                movement: SimpleMove {
-                    index,
-                    destination,
-                    capture: None,
-                },
-                castling_rook: None,
-                promotion: None,
-                en_passant_target: ((destination - self.position)
-                    == Vector::pawn_double_move(self.color()))
-                .then(|| {
-                    self.position
-                        .add_checked(Vector::pawn_single_move(self.color()))
-                        .unwrap()
-                })
-                .filter(|en_passant_target| {
-                    board.can_attack_by_pawn(*en_passant_target, !self.color())
-                }),
-                castling_right: board.castling_right,
-            })
-            .chain(
-                Vector::pawn_attacks(self.color())
-                    .into_iter()
-                    .filter_map(move |movement| self.position.add_checked(movement))
-                    .filter_map(move |destination| {
-                        if Some(destination) == board.en_passant_target
-                            && destination.y() == Coord::en_passant_target(!self.color())
-                        {
-                            let capture = board
-                                .get_index_with_kind(
-                                    destination
-                                        .add_checked(Vector::pawn_single_move(!self.color()))
-                                        .unwrap(),
-                                    !self.color(),
-                                    PieceKind::Pawn,
-                                )
-                                .expect("pawn that performed double move not found");
-                            Some(SimpleMove {
-                                index,
-                                destination,
-                                capture: Some(capture),
-                            })
-                        } else {
-                            let capture = board[destination]?;
-                            (board[capture].unwrap().color() != self.color()).then_some(
-                                SimpleMove {
-                                    index,
-                                    destination,
-                                    capture: Some(capture),
-                                },
-                            )
-                        }
-                    })
-                    .map(|movement| movement.to_simple_move(board.castling_right)),
-            )
-            .flat_map(move |movement| {
-                let promotion_choices: &'static [_] = if movement.movement.destination.y()
-                    == Coord::pawn_promotion_rank(self.color())
-                {
-                    &[
-                        Some(PieceKind::Queen),
-                        Some(PieceKind::Rook),
-                        Some(PieceKind::Bishop),
-                        Some(PieceKind::Knight),
-                    ]
-                } else {
-                    &[None]
-                };
-                promotion_choices
-                    .iter()
-                    .copied()
-                    .map(move |promotion| Move {
-                        promotion,
-                        ..movement
-                    })
-            })
-    }
-    fn non_castling_moves(self, index: PieceIndex, board: &Board) -> impl Iterator<Item = Move> {
-        let moves: Box<dyn Iterator<Item = Move>> = match self.piece() {
-            PieceKind::Pawn => Box::new(self.pawn_moves(index, board)),
-            PieceKind::Knight => Box::new(
-                self.step_moves(index, board, &Vector::KNIGHT_MOVES)
-                    .map(|movement| movement.to_simple_move(board.castling_right)),
-            ),
-            PieceKind::Bishop => Box::new(
-                self.all_directional_moves(index, board, &Vector::BISHOP_DIRECTIONS)
-                    .map(|movement| movement.to_simple_move(board.castling_right)),
-            ),
-            PieceKind::Rook => {
-                let castling_right = if self.position.y() == Coord::home_rank(self.color()) {
-                    board
-                        .castling_right
-                        .to_removed(self.color(), self.position.x())
-                } else {
-                    board.castling_right
-                };
-                Box::new(
-                    self.all_directional_moves(index, board, &Vector::ROOK_DIRECTIONS)
-                        .map(move |movement| movement.to_simple_move(castling_right)),
-                )
-            }
-            PieceKind::Queen => Box::new(
-                self.all_directional_moves(index, board, &Vector::QUEEN_DIRECTIONS)
-                    .map(|movement| movement.to_simple_move(board.castling_right)),
-            ),
-            PieceKind::King => {
-                let castling_right = board.castling_right.to_cleared(self.color());
-                Box::new(
-                    self.step_moves(index, board, &Vector::KING_MOVES)
-                        .map(move |movement| movement.to_simple_move(castling_right)),
-                )
-            }
-        };
-        moves.map(move |movement| {
-            if let Some(capture) = movement.movement.capture {
-                Move {
-                    castling_right: movement.castling_right.to_removed_for_rook_capture(
-                        board[capture].expect("captured piece not found"),
-                    ),
-                    ..movement
-                }
-            } else {
-                movement
-            }
-        })
-    }
-    fn can_be_blocked(self, target: Coord, blocker: Coord) -> bool {
-        self.position == blocker
-            || (matches!(
-                self.piece(),
-                PieceKind::Bishop | PieceKind::Rook | PieceKind::Queen
-            ) && (target - self.position).is_aligned(blocker - self.position)
-                && blocker.is_inside_of(self.position, target))
-    }
-    fn controlled_squares(self, board: &Board) -> Box<dyn Iterator<Item = Coord> + '_> {
+    fn controlled_squares(self, occupied: Bitboard) -> Bitboard {
         match self.piece() {
-            PieceKind::Pawn => Box::new(
-                Vector::pawn_attacks(self.color())
-                    .into_iter()
-                    .filter_map(move |movement| self.position.add_checked(movement)),
-            ),
-            PieceKind::Knight => Box::new(self.controlled_squares_step(&Vector::KNIGHT_MOVES)),
-            PieceKind::Bishop => {
-                Box::new(self.all_controlled_squares_direction(board, &Vector::BISHOP_DIRECTIONS))
-            }
-            PieceKind::Rook => {
-                Box::new(self.all_controlled_squares_direction(board, &Vector::ROOK_DIRECTIONS))
-            }
-            PieceKind::Queen => {
-                Box::new(self.all_controlled_squares_direction(board, &Vector::QUEEN_DIRECTIONS))
-            }
-            PieceKind::King => Box::new(self.controlled_squares_step(&Vector::KING_MOVES)),
+            PieceKind::Pawn => pawn_attacks(self.position, self.color()),
+            PieceKind::Knight => knight_attacks(self.position),
+            PieceKind::Bishop => bishop_attacks(self.position, occupied),
+            PieceKind::Rook => rook_attacks(self.position, occupied),
+            PieceKind::Queen => queen_attacks(self.position, occupied),
+            PieceKind::King => king_attacks(self.position),
         }
     }
-    fn pawn_attack_destination(
-        self,
-        victim: Coord,
-        board: &Board,
-    ) -> Box<dyn Iterator<Item = Coord> + '_> {
-        let difference = victim - self.position;
-        if difference.y == Vector::pawn_direction(self.color()) * 3 {
-            let destination = self
-                .position
-                .add_checked(Vector::pawn_double_move(self.color()))
-                .unwrap();
-            if self.position.y() == Coord::pawn_home_rank(self.color())
-                && difference.x.unsigned_abs() == 1
-                && !self
-                    .position
-                    .line_ex_in(destination, Vector::pawn_single_move(self.color()))
-                    .any(|position| board[position].is_some())
-            {
-                Box::new(once(destination))
-            } else {
-                Box::new(empty())
-            }
-        } else if difference.y == Vector::pawn_direction(self.color()) * 2 {
-            match difference.x {
-                x @ (-2 | 2) => {
-                    let destination = self
-                        .position
-                        .add_checked(Vector {
-                            x: x.signum(),
-                            y: Vector::pawn_direction(self.color()),
-                        })
-                        .unwrap();
-                    if board
-                        .index(destination)
-                        .is_some_and(|piece| piece.color() != self.color())
-                    {
-                        Box::new(once(destination))
-                    } else {
-                        Box::new(empty())
-                    }
-                }
-                -1 | 1 => {
-                    let destination = self
-                        .position
-                        .add_checked(Vector::pawn_single_move(self.color()))
-                        .unwrap();
-                    if board[destination].is_none() {
-                        Box::new(once(destination))
-                    } else {
-                        Box::new(empty())
-                    }
+    fn pawn_pushes(self, occupied: Bitboard) -> Bitboard {
+        let mut pushes = Bitboard::EMPTY;
+        let single = self
+            .position
+            .add_checked(Vector::pawn_single_move(self.color()))
+            .unwrap();
+        if !occupied.contains(single) {
+            pushes.insert(single);
+            if self.position.y() == Coord::pawn_home_rank(self.color()) {
+                let double = single
+                    .add_checked(Vector::pawn_single_move(self.color()))
+                    .unwrap();
+                if !occupied.contains(double) {
+                    pushes.insert(double);
                 }
-                0 => Box::new(
-                    Vector::pawn_attacks(self.color())
-                        .into_iter()
-                        .filter_map(move |movement| self.position.add_checked(movement))
-                        .filter(move |destination| {
-                            board
-                                .index(*destination)
-                                .is_some_and(|piece| piece.color() != self.color())
-                        }),
-                ),
-                _ => Box::new(empty()),
             }
-        } else {
-            Box::new(empty())
         }
+        pushes
     }
-    fn rook_attack_destination_unfiltered(self, victim: Coord) -> Box<dyn Iterator<Item = Coord>> {
-        if self.position.x() == victim.x() || self.position.y() == victim.y() {
-            Box::new(empty())
+    fn rook_attack_destination(self, victim: Coord, occupied: Bitboard) -> Bitboard {
+        if rook_attacks(self.position, Bitboard::EMPTY).contains(victim) {
+            Bitboard::EMPTY
         } else {
-            Box::new(
-                [
-                    Coord::new(self.position.x(), victim.y()),
-                    Coord::new(victim.x(), self.position.y()),
-                ]
-                .into_iter(),
-            )
+            rook_attacks(self.position, occupied) & rook_attacks(victim, occupied)
         }
     }
-    fn bishop_attack_destination_unfiltered(
-        self,
-        victim: Coord,
-    ) -> Box<dyn Iterator<Item = Coord>> {
-        let rotated_position = self.position.rotate();
-        let rotated_attack = victim.rotate();
-        if rotated_position.x == rotated_attack.x || rotated_position.y == rotated_attack.y {
-            Box::new(empty())
+    fn bishop_attack_destination(self, victim: Coord, occupied: Bitboard) -> Bitboard {
+        if bishop_attacks(self.position, Bitboard::EMPTY).contains(victim) {
+            Bitboard::EMPTY
         } else {
-            Box::new(
-                [
-                    RotatedCoord {
-                        x: rotated_position.x,
-                        y: rotated_attack.y,
-                    },
-                    RotatedCoord {
-                        x: rotated_attack.x,
-                        y: rotated_position.y,
-                    },
-                ]
-                .into_iter()
-                .filter_map(RotatedCoord::rotate_back),
-            )
+            bishop_attacks(self.position, occupied) & bishop_attacks(victim, occupied)
         }
     }
-    fn attack_destination(
-        self,
-        victim: Coord,
-        board: &Board,
-    ) -> Box<dyn Iterator<Item = Coord> + '_> {
-        match self.piece() {
+    // Squares where the piece can move to and attack the victim from
+    fn attack_destination(self, victim: Coord, board: &Board) -> Bitboard {
+        let occupied = board.occupied();
+        let destinations = match self.piece() {
             PieceKind::Pawn => {
                 if INCLUDE_PAWN {
-                    self.pawn_attack_destination(victim, board)
+                    (self.pawn_pushes(occupied)
+                        | (pawn_attacks(self.position, self.color()) & board.color(!self.color())))
+                        & pawn_attacks(victim, !self.color())
                 } else {
-                    Box::new(empty())
+                    Bitboard::EMPTY
                 }
             }
-            PieceKind::Knight => Box::new(
-                Vector::KNIGHT_MOVES
-                    .into_iter()
-                    .filter_map(move |movement| self.position.add_checked(movement))
-                    .filter(move |destination| {
-                        (victim - *destination).is_knight_move()
-                            && board
-                                .index(*destination)
-                                .is_none_or(|piece| piece.color() != self.color())
-                    }),
-            ),
-            PieceKind::Bishop => Box::new(filter_attacks(
-                self.bishop_attack_destination_unfiltered(victim),
-                self,
-                victim,
-                board,
-            )),
-            PieceKind::Rook => Box::new(filter_attacks(
-                self.rook_attack_destination_unfiltered(victim),
-                self,
-                victim,
-                board,
-            )),
-            PieceKind::Queen => Box::new(filter_attacks(
-                self.rook_attack_destination_unfiltered(victim)
-                    .chain(self.bishop_attack_destination_unfiltered(victim)),
-                self,
-                victim,
-                board,
-            )),
+            PieceKind::Knight => knight_attacks(self.position) & knight_attacks(victim),
+            PieceKind::Bishop => self.bishop_attack_destination(victim, occupied),
+            PieceKind::Rook => self.rook_attack_destination(victim, occupied),
+            PieceKind::Queen => {
+                self.rook_attack_destination(victim, occupied)
+                    | self.bishop_attack_destination(victim, occupied)
+            }
             PieceKind::King => todo!(),
-        }
+        };
+        destinations & !board.color(self.color())
     }
 }
-fn filter_attacks(
-    destinations: impl Iterator<Item = Coord>,
-    attacker: Piece,
-    victim: Coord,
-    board: &Board,
-) -> impl Iterator<Item = Coord> {
-    destinations.filter(move |destination| {
-        board
-            .index(*destination)
-            .is_none_or(|piece| piece.color() != attacker.color())
-            && !attacker
-                .position
-                .line_ex_ex(*destination, (*destination - attacker.position).as_unit())
-                .chain(destination.line_ex_ex(victim, (victim - *destination).as_unit()))
-                .any(|position| board[position].is_some())
-    })
-}
 impl Display for Piece {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{} on {}", self.piece, self.position)?;
         Ok(())
     }
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct PieceIndex(NonZero<u8>);
-impl From<PieceIndex> for usize {
-    fn from(value: PieceIndex) -> Self {
-        (value.0.get() & 0b_11111) as usize
-    }
-}
-impl TryFrom<usize> for PieceIndex {
-    type Error = InvalidByte;
-
-    fn try_from(value: usize) -> Result<Self, Self::Error> {
-        if value > 32 {
-            return Err(InvalidByte);
-        }
-        let byte: u8 = value.try_into().unwrap();
-        Ok(PieceIndex(NonZero::new(byte | 0b_1000_0000).unwrap()))
-    }
-}
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Board {
-    pieces: [Option<Piece>; 32],
-    indices: OnceCell<SimpleBoard<Option<PieceIndex>>>,
+    squares: SimpleBoard<Option<ColoredPieceKind>>,
+    colors: [Bitboard; 2],
+    kinds: [Bitboard; 6],
     current_player: Color,
     castling_right: CastlingRight,
     en_passant_target: Option<Coord>,
-}
-fn original_piece_range(color: Color, piece: PieceKind) -> Range<usize> {
-    match (color, piece) {
-        (Color::White, PieceKind::King) => 0..1,
-        (Color::White, PieceKind::Queen) => 1..2,
-        (Color::White, PieceKind::Rook) => 2..4,
-        (Color::White, PieceKind::Bishop) => 4..6,
-        (Color::White, PieceKind::Knight) => 6..8,
-        (Color::White, PieceKind::Pawn) => 8..16,
-        (Color::Black, PieceKind::King) => 16..17,
-        (Color::Black, PieceKind::Queen) => 17..18,
-        (Color::Black, PieceKind::Rook) => 18..20,
-        (Color::Black, PieceKind::Bishop) => 20..22,
-        (Color::Black, PieceKind::Knight) => 22..24,
-        (Color::Black, PieceKind::Pawn) => 24..32,
-    }
+    half_move: u32,
+    full_move: u32,
+    zobrist: u64,
 }
 impl Board {
     pub fn starting_position() -> Self {
@@ -538,201 +147,81 @@ impl Board {
     pub fn current_player(&self) -> Color {
         self.current_player
     }
+    pub fn zobrist(&self) -> u64 {
+        self.zobrist
+    }
     pub fn as_hashable(&self) -> HashableBoard {
-        let mut board = SimpleBoard::default();
-        for piece in self.all_pieces() {
-            board[piece.position] = Some(piece.piece);
-        }
         HashableBoard {
-            board,
+            board: self.squares,
             current_player: self.current_player,
             castling_right: self.castling_right,
             en_passant_target: self.en_passant_target,
         }
     }
+    pub fn as_fen(&self) -> Fen {
+        Fen {
+            board: self.as_hashable(),
+            half_move: self.half_move,
+            full_move: self.full_move,
+        }
+    }
     pub fn index(&self, position: Coord) -> Option<ColoredPieceKind> {
-        self[position].map(|index| self[index].unwrap().piece)
+        self.squares[position]
     }
-    fn index_and_piece(&self, position: Coord) -> Option<(PieceIndex, ColoredPieceKind)> {
-        self[position].map(|index| (index, self[index].unwrap().piece))
+    // Pieces are identified by the square they stand on
+    fn get(&self, position: Coord) -> Option<Piece> {
+        self.squares[position].map(|piece| Piece { piece, position })
     }
-    fn range(&self, range: Range<usize>) -> impl Iterator<Item = Piece> {
-        self.pieces[range].iter().copied().flatten()
+    fn place(&mut self, piece: Piece) {
+        self.squares[piece.position] = Some(piece.piece);
+        self.colors[piece.color() as usize].insert(piece.position);
+        self.kinds[piece.piece() as usize - 1].insert(piece.position);
+        self.zobrist ^= zobrist::piece(piece.piece, piece.position);
     }
-    fn range_indexed(&self, range: Range<usize>) -> impl Iterator<Item = (PieceIndex, Piece)> {
-        let slice = &self.pieces[range.clone()];
-        range
-            .into_iter()
-            .map(|index| index.try_into().unwrap())
-            .zip(slice.iter().copied())
-            .filter_map(|(index, piece)| piece.map(|piece| (index, piece)))
+    fn lift(&mut self, position: Coord) -> Piece {
+        let piece = self.get(position).expect("piece not found");
+        self.squares[position] = None;
+        self.colors[piece.color() as usize].remove(piece.position);
+        self.kinds[piece.piece() as usize - 1].remove(piece.position);
+        self.zobrist ^= zobrist::piece(piece.piece, piece.position);
+        piece
     }
-    fn all_pieces(&self) -> impl Iterator<Item = Piece> {
-        self.pieces.iter().copied().flatten()
-    }
-    fn all_pieces_indexed(&self) -> impl Iterator<Item = (PieceIndex, Piece)> {
-        self.pieces
-            .iter()
-            .copied()
-            .enumerate()
-            .filter_map(|(i, piece)| piece.map(|piece| (i.try_into().unwrap(), piece)))
-    }
-    fn pieces(&self, color: Color) -> impl FusedIterator<Item = Piece> {
-        let slice = match color {
-            Color::White => &self.pieces[0..16],
-            Color::Black => &self.pieces[16..32],
-        };
-        slice.iter().copied().flatten()
-    }
-    fn non_kings(&self, color: Color) -> impl Iterator<Item = Piece> {
-        let slice = match color {
-            Color::White => &self.pieces[1..16],
-            Color::Black => &self.pieces[17..32],
-        };
-        slice.iter().copied().flatten()
+    fn color(&self, color: Color) -> Bitboard {
+        self.colors[color as usize]
     }
-    fn pieces_indexed(&self, color: Color) -> impl Iterator<Item = (PieceIndex, Piece)> {
-        match color {
-            Color::White => self.range_indexed(0..16),
-            Color::Black => self.range_indexed(16..32),
-        }
+    fn kind(&self, color: Color, piece: PieceKind) -> Bitboard {
+        self.colors[color as usize] & self.kinds[piece as usize - 1]
     }
-    fn pieces_by_kind(&self, color: Color, piece: PieceKind) -> impl Iterator<Item = Piece> {
-        let definite_pieces = if piece == PieceKind::Pawn {
-            self.range(0..0)
-        } else {
-            self.range(original_piece_range(color, piece))
-        };
-        definite_pieces.chain(
-            self.range(original_piece_range(color, PieceKind::Pawn))
-                .filter(move |item| item.piece() == piece),
-        )
+    fn occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
     }
-    fn pieces_by_kind_indexed(
-        &self,
-        color: Color,
-        piece: PieceKind,
-    ) -> impl Iterator<Item = (PieceIndex, Piece)> {
-        let range = if piece == PieceKind::Pawn {
-            0..0
-        } else {
-            original_piece_range(color, piece)
-        };
-        self.range_indexed(range).chain(
-            self.range_indexed(original_piece_range(color, PieceKind::Pawn))
-                .filter(move |(_, item)| item.piece() == piece),
-        )
+    fn pieces_in(&self, bitboard: Bitboard) -> impl Iterator<Item = Piece> {
+        bitboard
+            .into_iter()
+            .map(|position| self.get(position).unwrap())
     }
-    fn pieces_by_kinds(&self, color: Color, pieces: &[PieceKind]) -> impl Iterator<Item = Piece> {
-        pieces
-            .iter()
-            .copied()
-            .map(move |piece| {
-                if piece == PieceKind::Pawn {
-                    0..0
-                } else {
-                    original_piece_range(color, piece)
-                }
-            })
-            .flat_map(|range| self.range(range))
-            .chain(
-                self.range(original_piece_range(color, PieceKind::Pawn))
-                    .filter(move |piece| pieces.contains(&piece.piece())),
-            )
+    fn all_pieces(&self) -> impl Iterator<Item = Piece> {
+        self.pieces_in(self.occupied())
     }
-    fn get_with_kind_indexed(
-        &self,
-        position: Coord,
-        color: Color,
-        piece: PieceKind,
-    ) -> Option<(PieceIndex, Piece)> {
-        if let Some(indices) = self.indices.get() {
-            indices[position].map(|index| (index, self[index].unwrap()))
-        } else {
-            self.pieces_by_kind_indexed(color, piece)
-                .find(|(_, piece)| piece.position == position)
-        }
+    fn non_kings(&self, color: Color) -> impl Iterator<Item = Piece> {
+        self.pieces_in(self.color(color) & !self.kind(color, PieceKind::King))
     }
-    fn get_index_with_kind(
-        &self,
-        position: Coord,
-        color: Color,
-        piece: PieceKind,
-    ) -> Option<PieceIndex> {
-        if let Some(indices) = self.indices.get() {
-            indices[position]
-        } else {
-            self.pieces_by_kind_indexed(color, piece)
-                .find(|(_, piece)| piece.position == position)
-                .map(|(index, _)| index)
-        }
+    fn pieces(&self, color: Color) -> impl Iterator<Item = Piece> {
+        self.pieces_in(self.color(color))
     }
-    fn position_has(&self, position: Coord, color: Color, piece: PieceKind) -> bool {
-        if let Some(indices) = self.indices.get() {
-            indices[position].is_some_and(|index| {
-                let found_piece = self[index].unwrap();
-                found_piece.color() == color && found_piece.piece() == piece
-            })
-        } else {
-            self.pieces_by_kind(color, piece)
-                .any(|piece| piece.position == position)
-        }
+    fn pieces_by_kind(&self, color: Color, piece: PieceKind) -> impl Iterator<Item = Piece> {
+        self.pieces_in(self.kind(color, piece))
     }
-    fn any_moves_has(
-        &self,
-        position: Coord,
-        moves: &[Vector],
-        color: Color,
-        piece: PieceKind,
-    ) -> bool {
-        if let Some(indices) = self.indices.get() {
-            moves
-                .iter()
-                .copied()
-                .filter_map(|movement| position.add_checked(movement))
-                .any(|position| {
-                    indices[position].is_some_and(|index| {
-                        let found_piece = self[index].unwrap();
-                        found_piece.color() == color && found_piece.piece() == piece
-                    })
-                })
-        } else {
-            self.pieces_by_kind(color, piece)
-                .any(|piece| moves.contains(&(piece.position - position)))
-        }
+    fn get_with_kind(&self, position: Coord, color: Color, piece: PieceKind) -> Option<Piece> {
+        self.kind(color, piece)
+            .contains(position)
+            .then(|| self.get(position).unwrap())
     }
     fn can_attack_by_pawn(&self, position: Coord, color: Color) -> bool {
-        self.any_moves_has(
-            position,
-            &Vector::pawn_attacks(!color),
-            color,
-            PieceKind::Pawn,
-        )
-    }
-    fn indices(&self) -> &SimpleBoard<Option<PieceIndex>> {
-        self.indices.get_or_init(|| {
-            let mut board = SimpleBoard::default();
-            for (i, piece) in self.all_pieces_indexed() {
-                board[piece.position] = Some(i);
-            }
-            board
-        })
+        !(pawn_attacks(position, !color) & self.kind(color, PieceKind::Pawn)).is_empty()
     }
     fn king(&self, color: Color) -> Option<Piece> {
-        match color {
-            Color::White => self.pieces[0],
-            Color::Black => self.pieces[16],
-        }
-    }
-    fn king_indexed(&self, color: Color) -> Option<(PieceIndex, Piece)> {
-        match color {
-            Color::White => self.pieces[0].map(|piece| (0.try_into().unwrap(), piece)),
-            Color::Black => self.pieces[16].map(|piece| (16.try_into().unwrap(), piece)),
-        }
-    }
-    fn pawns(&self, color: Color) -> impl Iterator<Item = Piece> {
-        self.range(original_piece_range(color, PieceKind::Pawn))
-            .filter(|item| item.piece() == PieceKind::Pawn)
+        self.pieces_by_kind(color, PieceKind::King).next()
     }
     pub fn validate(&self) -> Result<(), InvalidBoard> {
         let (Some(king), Some(opponent_king)) = (
@@ -761,7 +250,7 @@ impl Board {
             self.castling_right.all(color).all(|x| {
                 king_on_home
                     && self
-                        .get_with_kind_indexed(
+                        .get_with_kind(
                             Coord::new(x, Coord::home_rank(color)),
                             color,
                             PieceKind::Rook,
@@ -775,102 +264,52 @@ impl Board {
             let (color, pawn_position) = en_passant_target
                 .pawn_from_en_passant_target()
                 .ok_or(InvalidBoard::InvalidEnPassantRank)?;
-            if !self.position_has(pawn_position, color, PieceKind::Pawn) {
+            if !self.kind(color, PieceKind::Pawn).contains(pawn_position) {
                 return Err(InvalidBoard::EnPassantPawnNotFound);
             }
         }
-        for pawn in self.pawns(Color::White).chain(self.pawns(Color::Black)) {
-            if Coord::HOME_RANKS.contains(&pawn.position.y()) {
+        for pawn in
+            self.kind(Color::White, PieceKind::Pawn) | self.kind(Color::Black, PieceKind::Pawn)
+        {
+            if Coord::HOME_RANKS.contains(&pawn.y()) {
                 return Err(InvalidBoard::PawnOnHomeRank);
             }
         }
         Ok(())
     }
-    fn attackers_with_inspect(
+    fn attackers_with_occupancy(
         &self,
         position: Coord,
         color: Color,
-        checker: impl Fn(Coord) -> bool + Clone,
-    ) -> impl FusedIterator<Item = Piece> {
-        self.pieces(color).filter(move |piece| match piece.piece() {
-            PieceKind::Pawn => (position - piece.position).is_pawn_attack(color),
-            PieceKind::Knight => (position - piece.position).is_knight_move(),
-            PieceKind::Bishop => piece
-                .position
-                .is_aligned_with_bishop(position)
-                .is_some_and(|mut inside| !inside.any(checker.clone())),
-            PieceKind::Rook => piece
-                .position
-                .is_aligned_with_rook(position)
-                .is_some_and(|mut inside| !inside.any(checker.clone())),
-            PieceKind::Queen => piece
-                .position
-                .is_aligned_with_queen(position)
-                .is_some_and(|mut inside| !inside.any(checker.clone())),
-            PieceKind::King => (position - piece.position).is_king_move(),
-        })
+        occupied: Bitboard,
+    ) -> Bitboard {
+        let diagonal = self.kind(color, PieceKind::Bishop) | self.kind(color, PieceKind::Queen);
+        let orthogonal = self.kind(color, PieceKind::Rook) | self.kind(color, PieceKind::Queen);
+        (pawn_attacks(position, !color) & self.kind(color, PieceKind::Pawn))
+            | (knight_attacks(position) & self.kind(color, PieceKind::Knight))
+            | (king_attacks(position) & self.kind(color, PieceKind::King))
+            | (bishop_attacks(position, occupied) & diagonal)
+            | (rook_attacks(position, occupied) & orthogonal)
     }
     fn attackers(&self, position: Coord, color: Color) -> impl FusedIterator<Item = Piece> {
-        self.attackers_with_inspect(position, color, |position| self[position].is_some())
-    }
-    fn is_move_attacked(&self, indices: &[PieceIndex], destination: Coord, color: Color) -> bool {
-        self.attackers_with_inspect(destination, color, |position| {
-            self[position].is_some_and(|index| !indices.contains(&index))
-        })
-        .next()
-        .is_some()
-    }
-    fn pinned_with_inspect(
-        &self,
-        king: Coord,
-        pinned_position: Coord,
-        color: Color,
-        checker: impl Fn(Coord) -> bool + Clone,
-    ) -> Option<impl Iterator<Item = Coord>> {
-        let direction = pinned_position - king;
-        if Vector::QUEEN_DIRECTIONS
+        self.attackers_with_occupancy(position, color, self.occupied())
             .into_iter()
-            .any(|valid_direction| direction.is_aligned(valid_direction))
-        {
-            let direction = direction.as_unit();
-            if king
-                .line_ex_ex(pinned_position, direction)
-                .any(checker.clone())
-            {
-                None
-            } else {
-                let pieces = if Vector::BISHOP_DIRECTIONS.contains(&direction) {
-                    &[PieceKind::Bishop, PieceKind::Queen]
-                } else {
-                    &[PieceKind::Rook, PieceKind::Queen]
-                };
-                self.pieces_by_kinds(!color, pieces)
-                    .find_map(|pinning_piece| {
-                        if direction.is_aligned(pinning_piece.position - king)
-                            && pinned_position.is_inside_of(pinning_piece.position, king)
-                        {
-                            (!pinned_position
-                                .line_ex_ex(pinning_piece.position, direction)
-                                .any(checker.clone()))
-                            .then(|| king.line_ex_in(pinning_piece.position, direction))
-                        } else {
-                            None
-                        }
-                    })
+            .map(|position| self.get(position).unwrap())
+    }
+    fn pinned(&self, king: Coord, color: Color) -> Bitboard {
+        let occupied = self.occupied();
+        let diagonal = self.kind(!color, PieceKind::Bishop) | self.kind(!color, PieceKind::Queen);
+        let orthogonal = self.kind(!color, PieceKind::Rook) | self.kind(!color, PieceKind::Queen);
+        let pinning_pieces = (bishop_attacks(king, Bitboard::EMPTY) & diagonal)
+            | (rook_attacks(king, Bitboard::EMPTY) & orthogonal);
+        let mut pinned = Bitboard::EMPTY;
+        for pinning_piece in pinning_pieces {
+            let blockers = between(king, pinning_piece) & occupied;
+            if blockers.count() == 1 {
+                pinned |= blockers & self.color(color);
             }
-        } else {
-            None
         }
-    }
-    fn valid_destinations_when_pinned(
-        &self,
-        king: Coord,
-        pinned_position: Coord,
-        color: Color,
-    ) -> Option<impl Iterator<Item = Coord>> {
-        self.pinned_with_inspect(king, pinned_position, color, |position| {
-            self[position].is_some()
-        })
+        pinned
     }
     fn one_side_is_dead(&self, color: Color) -> Option<bool> {
         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -895,6 +334,13 @@ impl Board {
             })
             .map(|piece_left| piece_left.is_none())
     }
+    // Positions without them are prone to zugzwang
+    pub fn has_non_pawn_material(&self, color: Color) -> bool {
+        !(self.color(color)
+            & !self.kind(color, PieceKind::King)
+            & !self.kind(color, PieceKind::Pawn))
+        .is_empty()
+    }
     fn has_lone_king(&self, color: Color) -> bool {
         self.non_kings(color).next().is_none()
     }
@@ -905,36 +351,52 @@ impl Board {
             None => false,
         }
     }
-    pub fn valid_moves(&self) -> Result<impl Iterator<Item = Move>, EndState> {
+    pub fn valid_moves(&self) -> Result<impl Iterator<Item = Move> + use<>, EndState> {
         if self.is_dead() {
-            Err(EndState::Draw)
+            Err(EndState::Draw(EndReason::DeadPosition))
         } else {
             let (valid_moves, check) = self.valid_moves_and_check();
-            let mut valid_moves = valid_moves.peekable();
-            if valid_moves.peek().is_some() {
-                Ok(valid_moves)
-            } else if check {
-                Err(EndState::Win(!self.current_player))
+            if valid_moves.is_empty() {
+                if check {
+                    Err(EndState::Win(!self.current_player, EndReason::Checkmate))
+                } else {
+                    Err(EndState::Draw(EndReason::Stalemate))
+                }
+            } else if self.half_move >= MOVE_RULE_PLIES {
+                Err(EndState::Draw(EndReason::MoveRule))
             } else {
-                Err(EndState::Draw)
+                Ok(valid_moves.into_iter())
             }
         }
     }
+    pub fn legal_moves(&self) -> impl Iterator<Item = Move> + use<> {
+        let (legal_moves, _) = self.valid_moves_and_check();
+        legal_moves.into_iter()
+    }
+    pub fn is_check(&self) -> bool {
+        let king = self.king(self.current_player).unwrap();
+        self.attackers(king.position, !self.current_player)
+            .next()
+            .is_some()
+    }
     pub fn end_state(&self) -> Option<EndState> {
         self.valid_moves().err()
     }
-    fn castling_moves(&self, check: bool) -> impl Iterator<Item = Move> {
-        let (king_index, king) = self
-            .king_indexed(self.current_player)
-            .expect("king not found");
+    pub fn end_state_with_history(&self, history: &History) -> Option<EndState> {
+        self.end_state().or_else(|| {
+            (history.count(self.zobrist) + 1 >= REPETITION_DRAW)
+                .then_some(EndState::Draw(EndReason::Repetition))
+        })
+    }
+    fn castling_moves(&self, check: bool, king: Piece) -> impl Iterator<Item = Move> {
         let castling_right = self.castling_right;
         let new_castling_right = castling_right.to_cleared(self.current_player);
         castling_right
             .all(self.current_player)
             .filter(move |_| !check)
             .filter_map(move |x| {
-                let (rook_index, rook) = self
-                    .get_with_kind_indexed(
+                let rook = self
+                    .get_with_kind(
                         Coord::new(x, Coord::home_rank(self.current_player)),
                         self.current_player,
                         PieceKind::Rook,
@@ -965,45 +427,25 @@ impl Board {
                             ),
                         ),
                     };
-                [
-                    (
-                        king.position,
-                        king_destination,
-                        rook.position,
-                        PieceKind::King,
-                    ),
-                    (
-                        rook.position,
-                        rook_destination,
-                        king.position,
-                        PieceKind::Rook,
-                    ),
-                ]
-                .into_iter()
-                .all(|(origin, destination, other_position, piece)| {
-                    let direction = (destination - origin).as_unit();
-                    debug_assert_eq!(
-                        direction.y, 0,
-                        "{origin} and {destination} are not in the same rank",
-                    );
-                    origin.line_ex_in(destination, direction).all(|position| {
-                        (position == other_position || self[position].is_none())
-                            && (piece != PieceKind::King
-                                || !self.is_move_attacked(
-                                    &[king_index, rook_index],
-                                    position,
-                                    !self.current_player,
-                                ))
-                    })
-                })
+                let occupied =
+                    self.occupied() ^ Bitboard::from(king.position) ^ Bitboard::from(rook.position);
+                let king_path =
+                    between(king.position, king_destination) | Bitboard::from(king_destination);
+                let rook_path =
+                    between(rook.position, rook_destination) | Bitboard::from(rook_destination);
+                (((king_path | rook_path) & occupied).is_empty()
+                    && king_path.into_iter().all(|position| {
+                        self.attackers_with_occupancy(position, !self.current_player, occupied)
+                            .is_empty()
+                    }))
                 .then_some(Move {
                     movement: SimpleMove {
-                        index: king_index,
+                        origin: king.position,
                         destination: king_destination,
                         capture: None,
                     },
                     castling_rook: Some(SimpleMove {
-                        index: rook_index,
+                        origin: rook.position,
                         destination: rook_destination,
                         capture: None,
                     }),
@@ -1013,109 +455,260 @@ impl Board {
                 })
             })
     }
-    fn valid_moves_and_check(&self) -> (impl Iterator<Item = Move> + '_, bool) {
-        let king = self.king(self.current_player).expect("king not found");
-        let mut attackers_iter = self.attackers(king.position, !self.current_player).fuse();
-        let attackers = [attackers_iter.next(), attackers_iter.next()];
-        debug_assert_eq!(
-            attackers_iter.next(),
-            None,
+    fn new_move(&self, piece: Piece, destination: Coord, capture: Option<Coord>) -> Move {
+        let castling_right = match piece.piece() {
+            PieceKind::King => self.castling_right.to_cleared(piece.color()),
+            PieceKind::Rook if piece.position.y() == Coord::home_rank(piece.color()) => self
+                .castling_right
+                .to_removed(piece.color(), piece.position.x()),
+            _ => self.castling_right,
+        };
+        let castling_right = if let Some(capture) = capture {
+            castling_right
+                .to_removed_for_rook_capture(self.get(capture).expect("captured piece not found"))
+        } else {
+            castling_right
+        };
+        SimpleMove {
+            origin: piece.position,
+            destination,
+            capture,
+        }
+        .to_simple_move(castling_right)
+    }
+    fn pawn_moves(&self, pawn: Piece, king: Coord, mask: Bitboard, moves: &mut Vec<Move>) {
+        let color = pawn.color();
+        let occupied = self.occupied();
+        let mut push_pawn_move = |movement: Move| {
+            if movement.movement.destination.y() == Coord::pawn_promotion_rank(color) {
+                moves.extend(
+                    [
+                        PieceKind::Queen,
+                        PieceKind::Rook,
+                        PieceKind::Bishop,
+                        PieceKind::Knight,
+                    ]
+                    .map(|promotion| Move {
+                        promotion: Some(promotion),
+                        ..movement
+                    }),
+                );
+            } else {
+                moves.push(movement);
+            }
+        };
+        for destination in pawn.pawn_pushes(occupied) & mask {
+            let mut movement = self.new_move(pawn, destination, None);
+            if destination - pawn.position == Vector::pawn_double_move(color) {
+                let en_passant_target = pawn
+                    .position
+                    .add_checked(Vector::pawn_single_move(color))
+                    .unwrap();
+                movement.en_passant_target = self
+                    .can_attack_by_pawn(en_passant_target, !color)
+                    .then_some(en_passant_target);
+            }
+            push_pawn_move(movement);
+        }
+        let attacks = pawn_attacks(pawn.position, color);
+        for destination in attacks & self.color(!color) & mask {
+            push_pawn_move(self.new_move(pawn, destination, Some(destination)));
+        }
+        if let Some(destination) = self.en_passant_target
+            && destination.y() == Coord::en_passant_target(!color)
+            && attacks.contains(destination)
+        {
+            let captured = destination
+                .add_checked(Vector::pawn_single_move(!color))
+                .unwrap();
+
+            // en passant removes two pieces from a line at once, so it is
+            // checked by playing it on the occupancy rather than with pins
+            let occupied = occupied ^ Bitboard::from(pawn.position) ^ Bitboard::from(captured)
+                | Bitboard::from(destination);
+            if (self.attackers_with_occupancy(king, !color, occupied) & !Bitboard::from(captured))
+                .is_empty()
+            {
+                debug_assert!(
+                    self.kind(!color, PieceKind::Pawn).contains(captured),
+                    "pawn that performed double move not found"
+                );
+                push_pawn_move(self.new_move(pawn, destination, Some(captured)));
+            }
+        }
+    }
+    fn valid_moves_and_check(&self) -> (Vec<Move>, bool) {
+        let color = self.current_player;
+        let king = self.king(color).expect("king not found");
+        let occupied = self.occupied();
+        let own = self.color(color);
+        let checkers = self.attackers_with_occupancy(king.position, !color, occupied);
+        debug_assert!(
+            checkers.count() <= 2,
             "more than 2 pieces checking the king"
         );
-        let check = attackers[0].is_some();
-        let non_castling_moves = self
-            .pieces_indexed(self.current_player)
-            .flat_map(move |(index, piece)| {
-                let valid_destination_when_pinned: Option<Rc<[_]>> =
-                    if piece.piece() == PieceKind::King {
-                        None
-                    } else {
-                        self.valid_destinations_when_pinned(
-                            king.position,
-                            piece.position,
-                            self.current_player,
-                        )
-                        .map(Iterator::collect)
-                    };
-                piece
-                    .non_castling_moves(index, self)
-                    .map(move |movement| (movement, piece, valid_destination_when_pinned.clone()))
-            })
-            .filter(move |(movement, piece, valid_destination_when_pinned)| {
-                if piece.piece() == PieceKind::King {
-                    !self.is_move_attacked(
-                        &[movement.movement.index],
-                        movement.movement.destination,
-                        !self.current_player,
-                    )
-                } else {
-                    attackers
-                        .into_iter()
-                        .map_while(|attacker| attacker)
-                        .all(|piece| {
-                            piece.can_be_blocked(king.position, movement.movement.destination)
-                        })
-                        && valid_destination_when_pinned
-                            .as_ref()
-                            .is_none_or(|valid_destinations| {
-                                valid_destinations.contains(&movement.movement.destination)
-                            })
-                        && {
-                            // special case for en passant when the captured pawn is pinned
-                            if let Some(index) = movement.movement.capture {
-                                let captured = self[index].expect("captured piece not found");
-
-                                // check if the captured piece is also located
-                                // on the piece's destination
-
-                                // otherwise, it's en passant and check for the pin
-                                (captured.position == movement.movement.destination)
-                                    || self
-                                        .pinned_with_inspect(
-                                            king.position,
-                                            captured.position,
-                                            piece.color(),
-                                            |position| {
-                                                position != piece.position
-                                                    && (position == movement.movement.destination
-                                                        || self[position].is_some())
-                                            },
-                                        )
-                                        .is_none()
-                            } else {
-                                true
-                            }
-                        }
+        let check = !checkers.is_empty();
+        let check_mask = match checkers.count() {
+            0 => Bitboard::FULL,
+            1 => {
+                let checker = checkers.first().unwrap();
+                between(king.position, checker) | Bitboard::from(checker)
+            }
+            _ => Bitboard::EMPTY,
+        };
+        let pinned = self.pinned(king.position, color);
+        let mut moves = Vec::new();
+        for piece in self.pieces(color) {
+            if piece.piece() == PieceKind::King {
+                let occupied = occupied ^ Bitboard::from(piece.position);
+                for destination in king_attacks(piece.position) & !own {
+                    if self
+                        .attackers_with_occupancy(destination, !color, occupied)
+                        .is_empty()
+                    {
+                        moves.push(self.new_move(
+                            piece,
+                            destination,
+                            self.index(destination).map(|_| destination),
+                        ));
+                    }
                 }
-            })
-            .map(|(movement, _, _)| movement);
-        (non_castling_moves.chain(self.castling_moves(check)), check)
+                continue;
+            }
+            let mask = if pinned.contains(piece.position) {
+                check_mask & ray(king.position, (piece.position - king.position).as_unit())
+            } else {
+                check_mask
+            };
+            if piece.piece() == PieceKind::Pawn {
+                self.pawn_moves(piece, king.position, mask, &mut moves);
+            } else {
+                for destination in piece.controlled_squares(occupied) & !own & mask {
+                    moves.push(self.new_move(
+                        piece,
+                        destination,
+                        self.index(destination).map(|_| destination),
+                    ));
+                }
+            }
+        }
+        moves.extend(self.castling_moves(check, king));
+        (moves, check)
     }
-    pub fn move_piece(&mut self, movement: Move) {
+    pub fn make(&mut self, movement: Move) -> Undo {
+        let undo = Undo {
+            movement,
+            piece: self.get(movement.movement.origin).expect("piece not found"),
+            captured: movement
+                .movement
+                .capture
+                .map(|position| self.get(position).expect("captured piece not found")),
+            rook: movement
+                .castling_rook
+                .map(|rook| self.get(rook.origin).expect("rook not found")),
+            castling_right: self.castling_right,
+            en_passant_target: self.en_passant_target,
+            half_move: self.half_move,
+            zobrist: self.zobrist,
+        };
         let current_player = self.current_player;
-        let piece = self[movement.movement.index]
-            .as_mut()
-            .expect("piece not found");
+        let mut piece = self.lift(movement.movement.origin);
+        let irreversible = piece.piece() == PieceKind::Pawn || movement.movement.capture.is_some();
+        if let Some(position) = movement.movement.capture {
+            self.lift(position);
+        }
+        let rook = movement
+            .castling_rook
+            .map(|movement| (movement, self.lift(movement.origin)));
         piece.position = movement.movement.destination;
         if let Some(promotion) = movement.promotion {
             piece.piece = ColoredPieceKind::new(current_player, promotion);
         }
-        if let Some(index) = movement.movement.capture {
-            self[index] = None;
-        }
-        if let Some(movement) = movement.castling_rook {
-            let rook = self[movement.index].as_mut().expect("rook not found");
+        self.place(piece);
+        if let Some((movement, mut rook)) = rook {
             rook.position = movement.destination;
+            self.place(rook);
+        }
+        self.zobrist ^= zobrist::en_passant_target(self.en_passant_target)
+            ^ zobrist::en_passant_target(movement.en_passant_target);
+        if self.castling_right != movement.castling_right {
+            self.zobrist ^= zobrist::castling_right(self.castling_right)
+                ^ zobrist::castling_right(movement.castling_right);
         }
+        self.zobrist ^=
+            zobrist::current_player(current_player) ^ zobrist::current_player(!current_player);
         self.en_passant_target = movement.en_passant_target;
         self.castling_right = movement.castling_right;
         self.current_player = !self.current_player;
-
-        self.indices = OnceCell::new();
+        if irreversible {
+            self.half_move = 0;
+        } else {
+            self.half_move += 1;
+        }
+        if current_player == Color::Black {
+            self.full_move += 1;
+        }
 
         if cfg!(debug_assertions) {
             self.validate().unwrap();
+            assert_eq!(
+                self.zobrist,
+                self.as_hashable().zobrist(),
+                "zobrist mismatch"
+            );
         }
+        undo
+    }
+    pub fn unmake(&mut self, undo: Undo) {
+        let movement = undo.movement;
+        self.lift(movement.movement.destination);
+        if let Some(rook) = movement.castling_rook {
+            self.lift(rook.destination);
+        }
+        self.place(undo.piece);
+        if let Some(rook) = undo.rook {
+            self.place(rook);
+        }
+        if let Some(captured) = undo.captured {
+            self.place(captured);
+        }
+        self.current_player = !self.current_player;
+        if self.current_player == Color::Black {
+            self.full_move -= 1;
+        }
+        self.castling_right = undo.castling_right;
+        self.en_passant_target = undo.en_passant_target;
+        self.half_move = undo.half_move;
+        self.zobrist = undo.zobrist;
+    }
+    // The null move, the current player moves nothing
+    pub fn pass(&mut self) -> PassUndo {
+        let undo = PassUndo {
+            en_passant_target: self.en_passant_target,
+            zobrist: self.zobrist,
+        };
+        self.zobrist ^= zobrist::en_passant_target(self.en_passant_target)
+            ^ zobrist::current_player(self.current_player)
+            ^ zobrist::current_player(!self.current_player);
+        self.en_passant_target = None;
+        if self.current_player == Color::Black {
+            self.full_move += 1;
+        }
+        self.current_player = !self.current_player;
+        self.half_move += 1;
+        undo
+    }
+    pub fn unpass(&mut self, undo: PassUndo) {
+        self.current_player = !self.current_player;
+        if self.current_player == Color::Black {
+            self.full_move -= 1;
+        }
+        self.half_move -= 1;
+        self.en_passant_target = undo.en_passant_target;
+        self.zobrist = undo.zobrist;
+    }
+    pub fn move_piece(&mut self, movement: Move) {
+        self.make(movement);
     }
     pub fn move_lan(&mut self, movement: Lan) {
         self.move_piece(movement.as_move(self));
@@ -1126,9 +719,9 @@ impl Board {
         new
     }
     pub fn estimate_moves_left(&self) -> f32 {
-        let pieces: u8 = self
+        let pieces: u16 = self
             .all_pieces()
-            .map(|piece| piece.piece().value().map_or(0, NonZero::get))
+            .map(|piece| piece.piece().value().map_or(0, |value| value.get().into()))
             .sum();
         <f32>::from(pieces) * <f32>::from(ESTIMATED_TOTAL_MOVES) / <f32>::from(STARTING_VALUE * 2)
     }
@@ -1137,27 +730,23 @@ impl Board {
         let mut black_score = Estimated::default();
         let mut white_pawn_advancement = [0; 8];
         let mut black_pawn_advancement = [0; 8];
-        let mut white_coverage = SimpleBoard::default();
-        let mut black_coverage = SimpleBoard::default();
+        let mut white_coverage = Bitboard::EMPTY;
+        let mut black_coverage = Bitboard::EMPTY;
+        let occupied = self.occupied();
         for piece in self.all_pieces() {
-            for attack in piece.controlled_squares(self) {
-                match piece.color() {
-                    Color::White => {
-                        white_coverage[attack] = true;
-                        white_score.square_control += 1;
-                    }
-                    Color::Black => {
-                        black_coverage[attack] = true;
-                        black_score.square_control += 1;
-                    }
+            let controlled_squares = piece.controlled_squares(occupied);
+            let square_control: i16 = controlled_squares.count().try_into().unwrap();
+            match piece.color() {
+                Color::White => {
+                    white_coverage |= controlled_squares;
+                    white_score.square_control += square_control;
+                }
+                Color::Black => {
+                    black_coverage |= controlled_squares;
+                    black_score.square_control += square_control;
                 }
             }
-            let value: i8 = piece
-                .piece()
-                .value()
-                .map_or(0, NonZero::get)
-                .try_into()
-                .unwrap();
+            let value: i16 = piece.piece().value().map_or(0, NonZero::get).into();
             match piece.color() {
                 Color::White => white_score.material += value,
                 Color::Black => black_score.material += value,
@@ -1192,13 +781,14 @@ impl Board {
                 Color::Black => white_coverage,
             };
             for piece in self.non_kings(color) {
-                for attack in piece.attack_destination(opponent.position, self) {
-                    if !coverage[attack] {
-                        match color {
-                            Color::White => white_score.king_safety += 1,
-                            Color::Black => black_score.king_safety += 1,
-                        }
-                    }
+                let king_safety: i16 = (piece.attack_destination(opponent.position, self)
+                    & !coverage)
+                    .count()
+                    .try_into()
+                    .unwrap();
+                match color {
+                    Color::White => white_score.king_safety += king_safety,
+                    Color::Black => black_score.king_safety += king_safety,
                 }
             }
         }
@@ -1221,7 +811,7 @@ impl Board {
         assert!(
             !valid_moves
                 .into_iter()
-                .any(|movement| self[movement.movement.index].unwrap().position == position),
+                .any(|movement| movement.movement.origin == position),
             "found valid move for piece in position {position}",
         );
     }
@@ -1241,30 +831,16 @@ impl Board {
             .valid_moves()
             .into_iter()
             .flatten()
-            .flat_map(|movement| movement.as_lan_iter(self))
+            .flat_map(Move::as_lan_iter)
             .collect();
         assert!(!valid_moves.contains(&lan), "`{lan}` is a valid move");
     }
 }
 impl Index<Coord> for Board {
-    type Output = Option<PieceIndex>;
+    type Output = Option<ColoredPieceKind>;
 
     fn index(&self, index: Coord) -> &Self::Output {
-        &self.indices()[index]
-    }
-}
-impl Index<PieceIndex> for Board {
-    type Output = Option<Piece>;
-
-    fn index(&self, index: PieceIndex) -> &Self::Output {
-        let index: usize = index.into();
-        &self.pieces[index]
-    }
-}
-impl IndexMut<PieceIndex> for Board {
-    fn index_mut(&mut self, index: PieceIndex) -> &mut Self::Output {
-        let index: usize = index.into();
-        &mut self.pieces[index]
+        &self.squares[index]
     }
 }
 impl IndexableBoard for Board {
@@ -1305,6 +881,15 @@ impl HashableBoard {
             en_passant_target: None,
         }
     }
+    pub fn zobrist(&self) -> u64 {
+        self.board
+            .into_positioned_values()
+            .filter_map(|(position, piece)| piece.map(|piece| zobrist::piece(piece, position)))
+            .fold(0, |key, item| key ^ item)
+            ^ zobrist::castling_right(self.castling_right)
+            ^ zobrist::en_passant_target(self.en_passant_target)
+            ^ zobrist::current_player(self.current_player)
+    }
     pub fn fix_castling_rights(&mut self) {
         for color in [Color::White, Color::Black] {
             let row = self.board.copy_row(Coord::home_rank(color));
@@ -1351,44 +936,30 @@ impl TryFrom<HashableBoard> for Board {
     type Error = InvalidBoard;
 
     fn try_from(value: HashableBoard) -> Result<Self, Self::Error> {
-        let mut pieces = [None; 32];
-        'upper: for (position, piece) in value.board.into_positioned_values() {
-            if let Some(piece) = piece {
-                let range = original_piece_range(piece.color(), piece.piece());
-                let pawn_range = match piece.piece() {
-                    PieceKind::King | PieceKind::Pawn => 0..0,
-                    _ => original_piece_range(piece.color(), PieceKind::Pawn),
-                };
-                let [piece_rack, pawn_rack] = pieces.get_disjoint_mut([range, pawn_range]).unwrap();
-                for square in piece_rack.iter_mut().chain(pawn_rack.iter_mut()) {
-                    if square.is_none() {
-                        *square = Some(Piece { piece, position });
-                        continue 'upper;
-                    }
-                }
-                match piece.piece() {
-                    PieceKind::Pawn => {
-                        if pieces[original_piece_range(piece.color(), PieceKind::Pawn)]
-                            .iter()
-                            .copied()
-                            .all(|piece| piece.unwrap().piece() == PieceKind::Pawn)
-                        {
-                            return Err(ExceededPieces::Pawn.into());
-                        }
-                        return Err(ExceededPieces::PromotedPiece.into());
-                    }
-                    PieceKind::King => return Err(ExceededPieces::King.into()),
-                    _ => return Err(ExceededPieces::PromotedPiece.into()),
-                }
-            }
-        }
         let mut board = Board {
-            pieces,
-            indices: OnceCell::new(),
+            squares: SimpleBoard::default(),
+            colors: [Bitboard::EMPTY; 2],
+            kinds: [Bitboard::EMPTY; 6],
             current_player: value.current_player,
             castling_right: value.castling_right,
             en_passant_target: value.en_passant_target,
+            half_move: 0,
+            full_move: 1,
+            zobrist: 0,
         };
+        for (position, piece) in value.board.into_positioned_values() {
+            if let Some(piece) = piece {
+                board.place(Piece { piece, position });
+            }
+        }
+        for color in [Color::White, Color::Black] {
+            if board.kind(color, PieceKind::King).count() > 1 {
+                return Err(ExceededPieces::King.into());
+            }
+            if board.kind(color, PieceKind::Pawn).count() > 8 {
+                return Err(ExceededPieces::Pawn.into());
+            }
+        }
         if let Some(en_passant_target) = board.en_passant_target {
             let color = Coord::en_passant_target_color(en_passant_target.y())
                 .ok_or(InvalidBoard::InvalidEnPassantRank)?;
@@ -1397,6 +968,17 @@ impl TryFrom<HashableBoard> for Board {
             }
         }
         board.validate()?;
+        board.zobrist = board.as_hashable().zobrist();
+        Ok(board)
+    }
+}
+impl TryFrom<Fen> for Board {
+    type Error = InvalidBoard;
+
+    fn try_from(value: Fen) -> Result<Self, Self::Error> {
+        let mut board: Board = value.board.try_into()?;
+        board.half_move = value.half_move;
+        board.full_move = value.full_move;
         Ok(board)
     }
 }
@@ -1419,9 +1001,9 @@ impl IndexableBoard for HashableBoard {
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct SimpleMove {
-    index: PieceIndex,
+    origin: Coord,
     destination: Coord,
-    capture: Option<PieceIndex>,
+    capture: Option<Coord>,
 }
 impl SimpleMove {
     fn to_simple_move(self, castling_right: CastlingRight) -> Move {
@@ -1434,6 +1016,24 @@ impl SimpleMove {
         }
     }
 }
+// Everything `Board::pass` changes that can't be recovered by passing back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PassUndo {
+    en_passant_target: Option<Coord>,
+    zobrist: u64,
+}
+// Everything `Board::make` changes that can't be derived from the move itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Undo {
+    movement: Move,
+    piece: Piece,
+    captured: Option<Piece>,
+    rook: Option<Piece>,
+    castling_right: CastlingRight,
+    en_passant_target: Option<Coord>,
+    half_move: u32,
+    zobrist: u64,
+}
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Move {
     movement: SimpleMove,
@@ -1443,25 +1043,31 @@ pub struct Move {
     castling_right: CastlingRight,
 }
 impl Move {
-    fn as_ambiguous_lan_pair(self, board: &Board) -> (Lan, Option<Lan>) {
-        let piece = board[self.movement.index].expect("piece not found");
+    pub fn is_castling(self) -> bool {
+        self.castling_rook.is_some()
+    }
+    pub fn is_capture(self) -> bool {
+        self.movement.capture.is_some()
+    }
+    pub fn is_promotion(self) -> bool {
+        self.promotion.is_some()
+    }
+    fn as_ambiguous_lan_pair(self) -> (Lan, Option<Lan>) {
         (
             Lan {
-                origin: piece.position,
+                origin: self.movement.origin,
                 destination: self.movement.destination,
                 promotion: self.promotion,
             },
             self.castling_rook.map(|rook| Lan {
-                origin: piece.position,
-                destination: board[rook.index]
-                    .expect("captured piece not found")
-                    .position,
+                origin: self.movement.origin,
+                destination: rook.origin,
                 promotion: self.promotion,
             }),
         )
     }
-    pub fn as_lan_pair(self, board: &Board) -> (Lan, Option<Lan>) {
-        let (regular, chess960) = self.as_ambiguous_lan_pair(board);
+    pub fn as_lan_pair(self) -> (Lan, Option<Lan>) {
+        let (regular, chess960) = self.as_ambiguous_lan_pair();
         if let Some(chess960) = chess960 {
             if regular.origin.x() == Coord::KING_ORIGIN
                 && Coord::ROOK_ORIGINS.contains(&chess960.destination.x())
@@ -1476,12 +1082,12 @@ impl Move {
             (regular, None)
         }
     }
-    pub fn as_lan_iter(self, board: &Board) -> impl Iterator<Item = Lan> {
-        let (first, second) = self.as_lan_pair(board);
+    pub fn as_lan_iter(self) -> impl Iterator<Item = Lan> {
+        let (first, second) = self.as_lan_pair();
         once(first).chain(second)
     }
-    pub fn as_lan(self, board: &Board) -> Lan {
-        let (regular, chess960) = self.as_ambiguous_lan_pair(board);
+    pub fn as_lan(self) -> Lan {
+        let (regular, chess960) = self.as_ambiguous_lan_pair();
         if let Some(chess960) = chess960 {
             if regular.origin.x() == Coord::KING_ORIGIN
                 && Coord::ROOK_ORIGINS.contains(&chess960.destination.x())
@@ -1502,13 +1108,32 @@ pub struct Lan {
     pub promotion: Option<PieceKind>,
 }
 impl Lan {
+    // 0 is never a valid move since the origin and destination are the same
+    pub fn to_bits(self) -> u16 {
+        (u16::from(self.origin.index()) << 9)
+            | (u16::from(self.destination.index()) << 3)
+            | self.promotion.map_or(0, |piece| piece as u16)
+    }
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "each field is masked before casting"
+    )]
+    pub fn from_bits(bits: u16) -> Option<Self> {
+        let origin = Coord::from_index((bits >> 9) as u8 & 0b11_1111);
+        let destination = Coord::from_index((bits >> 3) as u8 & 0b11_1111);
+        (origin != destination).then(|| Lan {
+            origin,
+            destination,
+            promotion: PieceKind::try_from(bits as u8 & 0b111).ok(),
+        })
+    }
     #[allow(
         clippy::too_many_lines,
         reason = "I hope the provided comments are enough"
     )]
     pub fn as_move(self, board: &Board) -> Move {
-        let (index, piece) = board.index_and_piece(self.origin).expect("piece not found");
-        let capture = board[self.destination];
+        let piece = board.get(self.origin).expect("piece not found");
+        let capture = board.get(self.destination);
 
         let movement;
         let castling_rook;
@@ -1516,7 +1141,7 @@ impl Lan {
 
         // Handle castling
         if let Some(rook) = capture
-            && board[rook].unwrap().piece == ColoredPieceKind::new(piece.color(), PieceKind::Rook)
+            && rook.piece == ColoredPieceKind::new(piece.color(), PieceKind::Rook)
         {
             // "King takes rook" castling configuration e.g. e1h1
             let (king_destination, rook_destination) =
@@ -1532,12 +1157,12 @@ impl Lan {
                     ),
                 };
             movement = SimpleMove {
-                index,
+                origin: self.origin,
                 destination: Coord::new(king_destination, self.origin.y()),
                 capture: None,
             };
             castling_rook = Some(SimpleMove {
-                index: rook,
+                origin: self.destination,
                 destination: Coord::new(rook_destination, self.origin.y()),
                 capture: None,
             });
@@ -1560,20 +1185,20 @@ impl Lan {
                     self.destination
                 ),
             };
-            let (rook, _) = board
-                .pieces_by_kind_indexed(piece.color(), PieceKind::Rook)
-                .find(|(_, rook)| {
+            let rook = board
+                .pieces_by_kind(piece.color(), PieceKind::Rook)
+                .find(|rook| {
                     rook.position.y() == self.origin.y()
                         && Ord::cmp(&self.origin.x(), &rook.position.x()) == king_rook_ord
                 })
                 .expect("rook not found");
             movement = SimpleMove {
-                index,
+                origin: self.origin,
                 destination: self.destination,
                 capture: None,
             };
             castling_rook = Some(SimpleMove {
-                index: rook,
+                origin: rook.position,
                 destination: Coord::new(rook_destination, self.origin.y()),
                 capture: None,
             });
@@ -1585,16 +1210,16 @@ impl Lan {
                     .destination
                     .add_checked(Vector::pawn_single_move(!piece.color()))
                     .unwrap();
-                Some(
-                    board
-                        .get_index_with_kind(pawn, !piece.color(), PieceKind::Pawn)
-                        .expect("pawn that performed double move not found"),
-                )
+                debug_assert!(
+                    board.kind(!piece.color(), PieceKind::Pawn).contains(pawn),
+                    "pawn that performed double move not found"
+                );
+                Some(pawn)
             } else {
-                capture
+                capture.map(|piece| piece.position)
             };
             movement = SimpleMove {
-                index,
+                origin: self.origin,
                 destination: self.destination,
                 capture,
             };
@@ -1611,8 +1236,8 @@ impl Lan {
                 castling_right = board.castling_right;
             }
         }
-        let castling_right = if let Some(index) = movement.capture {
-            castling_right.to_removed_for_rook_capture(board[index].unwrap())
+        let castling_right = if let Some(position) = movement.capture {
+            castling_right.to_removed_for_rook_capture(board.get(position).unwrap())
         } else {
             castling_right
         };
@@ -1741,16 +1366,12 @@ impl Error for InvalidBoard {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ExceededPieces {
-    PromotedPiece,
     Pawn,
     King,
 }
 impl Display for ExceededPieces {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            ExceededPieces::PromotedPiece => {
-                write!(f, "exceeded allowable number of promoted pieces")?;
-            }
             ExceededPieces::Pawn => write!(f, "found more than 8 pawns")?,
             ExceededPieces::King => write!(f, "found more than 1 kings")?,
         }
@@ -1798,7 +1419,15 @@ impl Error for ExceededPieces {}
 
 #[cfg(test)]
 mod test {
-    use crate::{board::Board, color::Color, end_state::EndState, fen::Fen};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    use crate::{
+        board::{Board, ExceededPieces},
+        color::Color,
+        end_state::{EndReason, EndState},
+        fen::Fen,
+        history::History,
+    };
 
     #[test]
     fn checkmate() {
@@ -1806,32 +1435,44 @@ mod test {
         let board: Board = board.board.try_into().unwrap();
         assert!(matches!(
             board.valid_moves(),
-            Err(EndState::Win(Color::Black))
+            Err(EndState::Win(Color::Black, EndReason::Checkmate))
         ));
     }
     #[test]
     fn stalemate() {
         let board: Fen = "3rkr2/8/r7/4K3/r7/8/8/8 w - - 0 1".parse().unwrap();
         let board: Board = board.board.try_into().unwrap();
-        assert!(matches!(board.valid_moves(), Err(EndState::Draw)));
+        assert!(matches!(
+            board.valid_moves(),
+            Err(EndState::Draw(EndReason::Stalemate))
+        ));
     }
     #[test]
     fn dead_position() {
         let board: Fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
         let board: Board = board.board.try_into().unwrap();
-        assert!(matches!(board.valid_moves(), Err(EndState::Draw)));
+        assert!(matches!(
+            board.valid_moves(),
+            Err(EndState::Draw(EndReason::DeadPosition))
+        ));
     }
     #[test]
     fn dead_position_with_knight() {
         let board: Fen = "1n2k3/8/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
         let board: Board = board.board.try_into().unwrap();
-        assert!(matches!(board.valid_moves(), Err(EndState::Draw)));
+        assert!(matches!(
+            board.valid_moves(),
+            Err(EndState::Draw(EndReason::DeadPosition))
+        ));
     }
     #[test]
     fn dead_position_with_bishops() {
         let board: Fen = "4kb2/4b3/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
         let board: Board = board.board.try_into().unwrap();
-        assert!(matches!(board.valid_moves(), Err(EndState::Draw)));
+        assert!(matches!(
+            board.valid_moves(),
+            Err(EndState::Draw(EndReason::DeadPosition))
+        ));
     }
     #[test]
     fn bishop_of_different_color_is_alive() {
@@ -1840,6 +1481,19 @@ mod test {
         assert!(board.valid_moves().is_ok());
     }
     #[test]
+    fn many_promoted_pieces() {
+        let board: Fen = "k7/8/8/8/8/8/PPPPPPPP/QQQ1K3 w - - 0 1".parse().unwrap();
+        let mut board: Board = board.board.try_into().unwrap();
+        board.move_assert("c1d1".parse().unwrap());
+        let board: Fen = "k7/8/8/8/8/8/PPPPPPPP/NNNNKNNN w - - 0 1".parse().unwrap();
+        assert!(Board::try_from(board.board).is_ok());
+        let board: Fen = "k7/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1".parse().unwrap();
+        assert_eq!(
+            Board::try_from(board.board),
+            Err(ExceededPieces::Pawn.into())
+        );
+    }
+    #[test]
     fn castling() {
         let board: Fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1".parse().unwrap();
         let mut board: Board = board.board.try_into().unwrap();
@@ -2078,4 +1732,123 @@ mod test {
         board.assert_move_is_invalid("g7e7".parse().unwrap());
         board.assert_move_is_invalid("g7g2".parse().unwrap());
     }
+    #[test]
+    fn move_counters() {
+        let board: Fen = "4k3/8/8/8/8/8/4P3/4K2R w K - 7 12".parse().unwrap();
+        let mut board: Board = board.try_into().unwrap();
+        board.move_assert("h1h2".parse().unwrap());
+        assert_eq!(
+            board.as_fen(),
+            "4k3/8/8/8/8/8/4P2R/4K3 b - - 8 12".parse().unwrap()
+        );
+        board.move_assert("e8d8".parse().unwrap());
+        assert_eq!(
+            board.as_fen(),
+            "3k4/8/8/8/8/8/4P2R/4K3 w - - 9 13".parse().unwrap()
+        );
+        board.move_assert("e2e4".parse().unwrap());
+        assert_eq!(
+            board.as_fen(),
+            "3k4/8/8/8/4P3/8/7R/4K3 b - - 0 13".parse().unwrap()
+        );
+    }
+    #[test]
+    fn fifty_move_rule() {
+        let board: Fen = "4k3/8/8/8/8/8/8/4K2R w - - 99 80".parse().unwrap();
+        let mut board: Board = board.try_into().unwrap();
+        assert!(board.valid_moves().is_ok());
+        board.move_assert("h1h2".parse().unwrap());
+        assert!(matches!(
+            board.valid_moves(),
+            Err(EndState::Draw(EndReason::MoveRule))
+        ));
+    }
+    #[test]
+    fn checkmate_takes_precedence_over_fifty_move_rule() {
+        let board: Fen = "4k3/R7/8/8/8/8/8/4K2R w - - 99 80".parse().unwrap();
+        let mut board: Board = board.try_into().unwrap();
+        board.move_assert("h1h8".parse().unwrap());
+        assert!(matches!(
+            board.valid_moves(),
+            Err(EndState::Win(Color::White, EndReason::Checkmate))
+        ));
+    }
+    #[test]
+    fn threefold_repetition() {
+        let mut board = Board::starting_position();
+        let mut history = History::new();
+        for _ in 0..2 {
+            for lan in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                assert_eq!(board.end_state_with_history(&history), None);
+                history.push(board.zobrist());
+                board.move_assert(lan.parse().unwrap());
+            }
+        }
+        assert_eq!(
+            board.end_state_with_history(&history),
+            Some(EndState::Draw(EndReason::Repetition))
+        );
+    }
+    #[test]
+    fn make_unmake_round_trip() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut board = Board::chess960(rng.random_range(0..960));
+            for _ in 0..200 {
+                let Ok(moves) = board.valid_moves() else {
+                    break;
+                };
+                let moves: Box<[_]> = moves.collect();
+                for movement in &moves {
+                    let original = board.clone();
+                    let hashable = board.as_hashable();
+                    let undo = board.make(*movement);
+                    board.unmake(undo);
+                    assert_eq!(board.as_hashable(), hashable);
+                    assert_eq!(board, original);
+                }
+                board.make(moves[rng.random_range(0..moves.len())]);
+            }
+        }
+    }
+    #[test]
+    fn zobrist_transposition() {
+        let mut board_1 = Board::starting_position();
+        let mut board_2 = Board::starting_position();
+        for lan in ["g1f3", "g8f6", "b1c3"] {
+            board_1.move_assert(lan.parse().unwrap());
+        }
+        for lan in ["b1c3", "g8f6", "g1f3"] {
+            board_2.move_assert(lan.parse().unwrap());
+        }
+        assert_eq!(board_1.zobrist(), board_2.zobrist());
+        assert_eq!(board_1.zobrist(), board_1.as_hashable().zobrist());
+        assert_ne!(board_1.zobrist(), Board::starting_position().zobrist());
+    }
+    #[test]
+    fn pass() {
+        let board: Fen = "4k3/8/8/8/4Pp2/8/8/4K3 b - e3 0 1".parse().unwrap();
+        let mut board: Board = board.try_into().unwrap();
+        let original = board.clone();
+        let undo = board.pass();
+        assert_eq!(
+            board.as_fen(),
+            "4k3/8/8/8/4Pp2/8/8/4K3 w - - 1 2".parse().unwrap()
+        );
+        assert_eq!(board.zobrist(), board.as_hashable().zobrist());
+        board.unpass(undo);
+        assert_eq!(board, original);
+    }
+    #[test]
+    fn zobrist_en_passant_and_castling() {
+        let board: Fen = "r3k2r/8/8/8/5p2/8/4P3/R3K2R w KQkq - 0 1".parse().unwrap();
+        let mut board: Board = board.try_into().unwrap();
+        board.move_assert("e2e4".parse().unwrap());
+        let without_en_passant: Fen = "r3k2r/8/8/8/4Pp2/8/8/R3K2R b KQkq - 0 1".parse().unwrap();
+        let without_en_passant: Board = without_en_passant.try_into().unwrap();
+        assert_ne!(board.zobrist(), without_en_passant.zobrist());
+        let without_castling: Fen = "r3k2r/8/8/8/4Pp2/8/8/R3K2R b KQ e3 0 1".parse().unwrap();
+        let without_castling: Board = without_castling.try_into().unwrap();
+        assert_ne!(board.zobrist(), without_castling.zobrist());
+    }
 }
@@ -53,6 +53,7 @@ impl CastlingRight {
         let byte = self.byte_mut(color);
         *byte |= 0b_1 << x;
     }
+    #[allow(dead_code, reason = "counterpart of `to_removed`")]
     pub fn to_added(self, color: Color, x: u8) -> Self {
         let mut new = self;
         new.add(color, x);
@@ -105,6 +105,19 @@ impl Coord {
     pub fn y(self) -> u8 {
         (self.0.get() >> 3) & 0b_111
     }
+    pub fn file(self) -> char {
+        (self.x() + b'a') as char
+    }
+    pub fn rank(self) -> char {
+        (b'8' - self.y()) as char
+    }
+    pub fn index(self) -> u8 {
+        self.0.get() & 0b_0011_1111
+    }
+    pub fn from_index(index: u8) -> Self {
+        debug_assert!(index < 64, "{index} should be < 64");
+        Coord(NonZero::new(0b1000_0000 | index).unwrap())
+    }
     pub fn add_checked(self, movement: Vector) -> Option<Self> {
         Self::new_checked(
             self.x().checked_add_signed(movement.x)?,
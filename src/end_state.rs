@@ -1,17 +1,40 @@
 use std::fmt::{self, Display, Formatter};
 
-use crate::color::Color;
+use crate::{board::MOVE_RULE_PLIES, color::Color, history::REPETITION_DRAW};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndReason {
+    Checkmate,
+    Stalemate,
+    DeadPosition,
+    Repetition,
+    MoveRule,
+    // Decided outside of the board e.g. resignation, agreement, or time
+    Adjudication,
+}
+impl Display for EndReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EndReason::Checkmate => write!(f, "checkmate")?,
+            EndReason::Stalemate => write!(f, "stalemate")?,
+            EndReason::DeadPosition => write!(f, "dead position")?,
+            EndReason::Repetition => write!(f, "{REPETITION_DRAW}-fold repetition")?,
+            EndReason::MoveRule => write!(f, "{}-move rule", MOVE_RULE_PLIES / 2)?,
+            EndReason::Adjudication => write!(f, "adjudication")?,
+        }
+        Ok(())
+    }
+}
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum EndState {
-    Win(Color),
-    Draw,
+    Win(Color, EndReason),
+    Draw(EndReason),
 }
 impl Display for EndState {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            EndState::Win(color) => write!(f, "{color} wins")?,
-            EndState::Draw => write!(f, "draw")?,
+            EndState::Win(color, reason) => write!(f, "{color} wins by {reason}")?,
+            EndState::Draw(reason) => write!(f, "draw by {reason}")?,
         }
         Ok(())
     }
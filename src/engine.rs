@@ -1,8 +1,9 @@
 use std::{
+    io::Write,
     num::NonZero,
     sync::{
         Arc, RwLock,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicI64, Ordering},
         mpsc::{Receiver, Sender, channel, sync_channel},
     },
     thread::{sleep, spawn},
@@ -11,8 +12,10 @@ use std::{
 
 use crate::{
     board::{Board, Lan},
-    game_tree::{GameTree, Table},
+    game_tree::{Bound, GameTree, SearchOptions, Table},
     heuristics::Score,
+    perft::write_divide_with_stop_signal,
+    time_manager::{TimeLimit, TimeManager},
 };
 
 enum Input {
@@ -20,26 +23,43 @@ enum Input {
     SetBoard(Board),
     Move(Lan),
     Calculate {
+        time_manager: Option<TimeManager>,
         depth: Option<NonZero<u32>>,
         nodes: Option<NonZero<u32>>,
         mate_in_plies: Option<NonZero<u32>>,
+        search_moves: Option<Box<[Lan]>>,
         ponder: bool,
         info_callback: Box<dyn FnMut(Info) + Send>,
         best_move_callback: Box<dyn FnOnce(Option<Lan>, Option<Lan>) + Send>,
         stop_signal: Arc<AtomicBool>,
         call_best_move: Arc<AtomicBool>,
     },
-    SetHashMaxCapacity(usize),
+    Perft {
+        board: Board,
+        depth: u32,
+        output: Box<dyn Write + Send>,
+        stop_signal: Arc<AtomicBool>,
+    },
+    SetHashSize(usize),
     ClearHash,
+    SetHashVerification(bool),
     SetThread(NonZero<usize>),
+    SetMultiPv(NonZero<usize>),
+    SetBoundedMemory(bool),
 }
 pub struct Info {
     pub depth: NonZero<u32>,
     pub time: Duration,
     pub nodes: NonZero<u32>,
+    // Best first, has more than one line with multiple principal variations
+    pub lines: Box<[Line]>,
+    // in permille
+    pub hash_full: u32,
+}
+pub struct Line {
     pub pv: Box<[Lan]>,
     pub score: Option<Score>,
-    pub hash_capacity: usize,
+    pub bound: Bound,
 }
 #[derive(Debug)]
 pub struct Engine {
@@ -50,6 +70,10 @@ pub struct Engine {
     ponder: Arc<RwLock<Option<Lan>>>,
 }
 impl Engine {
+    #[allow(
+        clippy::too_many_lines,
+        reason = "the engine thread is a single event loop"
+    )]
     pub fn new() -> Self {
         let (input, input_receiver) = channel();
         let (ready_sender, ready) = sync_channel(0);
@@ -59,6 +83,8 @@ impl Engine {
             let mut game_tree = GameTree::new(Board::starting_position());
             let mut table = Table::new(0);
             let mut thread = 1;
+            let mut multi_pv = 1;
+            let mut options = SearchOptions::default();
             let mut last_depth = 1;
             for input in input_receiver {
                 match input {
@@ -71,37 +97,47 @@ impl Engine {
                     Input::SetBoard(board) => {
                         last_depth = 1;
                         game_tree = GameTree::new(board);
+                        game_tree.set_options(options);
                     }
                     Input::Move(movement) => {
                         last_depth = Ord::max(last_depth - 1, 1);
                         game_tree.move_piece(movement);
                     }
                     Input::Calculate {
+                        mut time_manager,
                         depth,
                         nodes: max_nodes,
                         mate_in_plies,
+                        search_moves,
                         ponder: ponder_mode,
                         mut info_callback,
                         best_move_callback,
                         stop_signal,
                         call_best_move,
                     } => {
+                        table.age();
                         let start = if let Some(movement) = game_tree.best_move() {
                             info_callback(Info {
                                 depth: NonZero::new(1).unwrap(),
                                 time: Duration::ZERO,
                                 nodes: NonZero::new(2).unwrap(),
-                                pv: [movement].into(),
-                                score: game_tree.score(),
-                                hash_capacity: table.capacity(),
+                                lines: [Line {
+                                    pv: [movement].into(),
+                                    score: game_tree.score(),
+                                    bound: game_tree.bound(),
+                                }]
+                                .into(),
+                                hash_full: table.hash_full(),
                             });
-                            match depth {
-                                Some(depth) => Ord::min(depth.get(), last_depth),
-                                None => last_depth,
-                            }
+                            [depth, mate_in_plies]
+                                .into_iter()
+                                .flatten()
+                                .fold(last_depth, |start, depth| Ord::min(start, depth.get()))
                         } else {
                             1
                         };
+                        let node_budget = max_nodes
+                            .map(|max_nodes| Arc::new(AtomicI64::new(max_nodes.get().into())));
                         for i in start.. {
                             last_depth = i;
                             let start = Instant::now();
@@ -109,21 +145,45 @@ impl Engine {
                                 i,
                                 &mut table,
                                 &stop_signal,
+                                node_budget.as_ref(),
                                 thread,
+                                multi_pv,
+                                search_moves.as_deref(),
                             );
+                            let Some(nodes) = NonZero::new(nodes) else {
+                                break;
+                            };
                             info_callback(Info {
                                 depth: NonZero::new(i).unwrap(),
                                 time: start.elapsed(),
-                                nodes: NonZero::new(nodes).unwrap(),
-                                pv: game_tree.best_line().collect(),
-                                score: game_tree.score(),
-                                hash_capacity: table.capacity(),
+                                nodes,
+                                lines: game_tree
+                                    .lines()
+                                    .take(multi_pv)
+                                    .map(|(score, bound, pv)| Line {
+                                        pv: pv.collect(),
+                                        score: Some(score),
+                                        bound,
+                                    })
+                                    .collect(),
+                                hash_full: table.hash_full(),
                             });
                             if stop_signal.load(Ordering::Relaxed)
                                 || depth.is_some_and(|depth| i >= depth.get())
-                                || max_nodes.is_some_and(|max_nodes| nodes >= max_nodes.get())
-                                || (game_tree.score().is_some_and(Score::is_win)
-                                    && mate_in_plies.is_some_and(|plies| i <= plies.get()))
+                                || node_budget
+                                    .as_ref()
+                                    .is_some_and(|budget| budget.load(Ordering::Relaxed) <= 0)
+                                || mate_in_plies.is_some_and(|plies| {
+                                    i >= plies.get()
+                                        || matches!(
+                                            (game_tree.score(), game_tree.current_player()),
+                                            (Some(Score::Win(color, mate)), Some(current_player))
+                                                if color == current_player && mate <= plies.get(),
+                                        )
+                                })
+                                || time_manager.as_mut().is_some_and(|time_manager| {
+                                    !time_manager.should_continue(&game_tree)
+                                })
                             {
                                 break;
                             }
@@ -144,9 +204,28 @@ impl Engine {
                             best_move_callback(movement, pondered_move);
                         }
                     }
-                    Input::SetHashMaxCapacity(capacity) => table.set_max_capacity(capacity),
-                    Input::ClearHash => table.clear_allocation(),
+                    Input::Perft {
+                        board,
+                        depth,
+                        mut output,
+                        stop_signal,
+                    } => {
+                        // a failed write means the output is gone, there is
+                        // no one left to report the error to
+                        let _ =
+                            write_divide_with_stop_signal(&mut output, &board, depth, &stop_signal);
+                    }
+                    Input::SetHashSize(size) => table.set_size(size),
+                    Input::ClearHash => table.clear(),
+                    Input::SetHashVerification(verification) => {
+                        table.set_verification(verification);
+                    }
                     Input::SetThread(new_value) => thread = new_value.get(),
+                    Input::SetMultiPv(new_value) => multi_pv = new_value.get(),
+                    Input::SetBoundedMemory(bounded_memory) => {
+                        options.bounded_memory = bounded_memory;
+                        game_tree.set_options(options);
+                    }
                 }
             }
         });
@@ -168,30 +247,38 @@ impl Engine {
     pub fn move_piece(&self, movement: Lan) {
         self.input.send(Input::Move(movement)).unwrap();
     }
+    #[allow(clippy::too_many_arguments)]
     pub fn calculate(
         &mut self,
-        duration: Option<Duration>,
+        time_limit: Option<TimeLimit>,
         depth: Option<NonZero<u32>>,
         nodes: Option<NonZero<u32>>,
         mate_in_plies: Option<NonZero<u32>>,
+        search_moves: Option<Box<[Lan]>>,
         ponder: bool,
         info_callback: impl FnMut(Info) + Send + 'static,
         best_move_callback: impl FnOnce(Option<Lan>, Option<Lan>) + Send + 'static,
     ) {
         let stop_signal = Arc::new(AtomicBool::new(false));
-        if let Some(duration) = duration {
+        let time_manager = time_limit.map(TimeManager::new);
+        if let Some(time_manager) = &time_manager {
+            // the soft limit is checked between iterations by the engine
+            // thread, the hard limit has to interrupt one
+            let hard_limit = time_manager.hard_limit();
             let stop_signal = stop_signal.clone();
             spawn(move || {
-                sleep(duration);
+                sleep(hard_limit);
                 stop_signal.store(true, Ordering::Relaxed);
             });
         }
         let call_best_move = Arc::new(AtomicBool::new(true));
         self.input
             .send(Input::Calculate {
+                time_manager,
                 depth,
                 nodes,
                 mate_in_plies,
+                search_moves,
                 ponder,
                 info_callback: Box::new(info_callback),
                 best_move_callback: Box::new(best_move_callback),
@@ -202,6 +289,20 @@ impl Engine {
         self.stop_signal = Some(stop_signal);
         self.call_best_move = Some(call_best_move);
     }
+    // Writes the node count of every root move, stopped with `Engine::stop`
+    pub fn perft(&mut self, board: Board, depth: u32, output: impl Write + Send + 'static) {
+        let stop_signal = Arc::new(AtomicBool::new(false));
+        self.input
+            .send(Input::Perft {
+                board,
+                depth,
+                output: Box::new(output),
+                stop_signal: stop_signal.clone(),
+            })
+            .unwrap();
+        self.stop_signal = Some(stop_signal);
+        self.call_best_move = None;
+    }
     pub fn stop(&self, call_best_move: bool) {
         // it's important to set this before the stop signal because the stop
         // signal is read first
@@ -216,15 +317,27 @@ impl Engine {
         self.ready();
         self.ponder.write().unwrap().take()
     }
-    pub fn set_hash_max_capacity(&self, max_capacity: usize) {
-        self.input
-            .send(Input::SetHashMaxCapacity(max_capacity))
-            .unwrap();
+    // In bytes
+    pub fn set_hash_size(&self, size: usize) {
+        self.input.send(Input::SetHashSize(size)).unwrap();
     }
     pub fn clear_hash(&self) {
         self.input.send(Input::ClearHash).unwrap();
     }
+    pub fn set_hash_verification(&self, verification: bool) {
+        self.input
+            .send(Input::SetHashVerification(verification))
+            .unwrap();
+    }
     pub fn set_thread(&self, thread: NonZero<usize>) {
         self.input.send(Input::SetThread(thread)).unwrap();
     }
+    pub fn set_multi_pv(&self, multi_pv: NonZero<usize>) {
+        self.input.send(Input::SetMultiPv(multi_pv)).unwrap();
+    }
+    pub fn set_bounded_memory(&self, bounded_memory: bool) {
+        self.input
+            .send(Input::SetBoundedMemory(bounded_memory))
+            .unwrap();
+    }
 }
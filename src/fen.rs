@@ -23,6 +23,37 @@ pub struct Fen {
     pub half_move: u32,
     pub full_move: u32,
 }
+impl Fen {
+    // Whether the castling rights can be written as `KQkq` without ambiguity
+    pub fn uses_standard_castling(&self) -> bool {
+        [Color::White, Color::Black].into_iter().all(|color| {
+            let row = self.board.board.copy_row(Coord::home_rank(color));
+            let king_in_position = row
+                .into_iter()
+                .position(|piece| piece == Some(ColoredPieceKind::new(color, PieceKind::King)))
+                == Some(Coord::KING_ORIGIN as usize);
+            self.board.castling_right.all(color).all(|rook| {
+                if king_in_position {
+                    let range = match rook {
+                        Coord::ROOK_ORIGIN_QUEENSIDE => {
+                            (Coord::ROOK_ORIGIN_QUEENSIDE + 1)..=(Coord::KING_ORIGIN - 1)
+                        }
+                        Coord::ROOK_ORIGIN_KINGSIDE => {
+                            (Coord::KING_ORIGIN + 1)..=(Coord::ROOK_ORIGIN_KINGSIDE - 1)
+                        }
+                        _ => return false,
+                    };
+                    !range.into_iter().any(|x| {
+                        let x: usize = x.into();
+                        row[x] == Some(ColoredPieceKind::new(color, PieceKind::Rook))
+                    })
+                } else {
+                    false
+                }
+            })
+        })
+    }
+}
 impl Display for Fen {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         for (first, row) in once(true)
@@ -59,33 +90,7 @@ impl Display for Fen {
             }
         }
         write!(f, " {}", self.board.current_player.lowercase())?;
-        let use_standard_castling = [Color::White, Color::Black].into_iter().all(|color| {
-            let row = self.board.board.copy_row(Coord::home_rank(color));
-            let king_in_position = row
-                .into_iter()
-                .position(|piece| piece == Some(ColoredPieceKind::new(color, PieceKind::King)))
-                == Some(Coord::KING_ORIGIN as usize);
-            self.board.castling_right.all(color).all(|rook| {
-                if king_in_position {
-                    let range = match rook {
-                        Coord::ROOK_ORIGIN_QUEENSIDE => {
-                            (Coord::ROOK_ORIGIN_QUEENSIDE + 1)..=(Coord::KING_ORIGIN - 1)
-                        }
-                        Coord::ROOK_ORIGIN_KINGSIDE => {
-                            (Coord::KING_ORIGIN + 1)..=(Coord::ROOK_ORIGIN_KINGSIDE - 1)
-                        }
-                        _ => return false,
-                    };
-                    !range.into_iter().any(|x| {
-                        let x: usize = x.into();
-                        row[x] == Some(ColoredPieceKind::new(color, PieceKind::Rook))
-                    })
-                } else {
-                    false
-                }
-            })
-        });
-        if use_standard_castling {
+        if self.uses_standard_castling() {
             write!(f, " {}", self.board.castling_right.standard_fen_display())?;
         } else {
             write!(f, " {}", self.board.castling_right)?;
@@ -2,10 +2,9 @@ use rand::{Rng, SeedableRng, rngs::SmallRng};
 use rustc_hash::FxHashSet;
 
 use crate::{
-    board::{Board, Lan},
+    board::{Board, HashableBoard, Lan, Move},
     board_display::BoardDisplay,
     coord::Coord,
-    fen::Fen,
     piece::PieceKind,
 };
 
@@ -38,53 +37,69 @@ impl From<chess::ChessMove> for Lan {
         }
     }
 }
+// Random games are taken back after this many plies
+const MAX_PLIES: usize = 400;
+
+// Plays random games on a single board with `Board::make`, comparing the legal
+// moves against the `chess` crate and checking that `Board::unmake` restores
+// the position after every move
 pub fn fuzz() {
     let mut board = Board::starting_position();
     let mut rng = SmallRng::from_os_rng();
+    let mut played = Vec::new();
     loop {
-        let moves: FxHashSet<_> = board
-            .valid_moves()
-            .into_iter()
-            .flatten()
-            .map(|movement| movement.as_lan(&board))
-            .collect();
-        if moves.is_empty() {
-            board = Board::starting_position();
-            continue;
-        }
-        let board2: chess::Board = Fen {
-            board: board.as_hashable(),
-            half_move: 0,
-            full_move: 1,
+        let moves: Box<[Move]> = board.legal_moves().collect();
+        compare_legal_moves(&board, &moves);
+        let hashable = board.as_hashable();
+        let zobrist = board.zobrist();
+        for movement in &moves {
+            let undo = board.make(*movement);
+            board.unmake(undo);
+            assert_restored(&board, hashable, zobrist, movement.as_lan());
         }
-        .to_string()
-        .parse()
-        .unwrap();
-        let moves2: FxHashSet<Lan> = chess::MoveGen::new_legal(&board2).map(Into::into).collect();
-        if let Some(movement) = moves.difference(&moves2).next() {
-            panic!(
-                "found {movement} but it's not a legal move\n{}\n{}",
-                BoardDisplay::new(&board),
-                Fen {
-                    board: board.as_hashable(),
-                    half_move: 0,
-                    full_move: 1,
-                }
-            );
-        }
-        if let Some(movement) = moves2.difference(&moves).next() {
-            panic!(
-                "{movement} not found\n{}\n{}",
-                BoardDisplay::new(&board),
-                Fen {
-                    board: board.as_hashable(),
-                    half_move: 0,
-                    full_move: 1,
-                }
-            );
+        if moves.is_empty() || played.len() >= MAX_PLIES {
+            while let Some((undo, movement, hashable, zobrist)) = played.pop() {
+                board.unmake(undo);
+                assert_restored(&board, hashable, zobrist, movement);
+            }
+            assert_eq!(board, Board::starting_position());
+            continue;
         }
-        let moves: Box<[_]> = moves.into_iter().collect();
         let movement = moves[rng.random_range(0..moves.len())];
-        board.move_lan(movement);
+        let undo = board.make(movement);
+        played.push((undo, movement.as_lan(), hashable, zobrist));
     }
 }
+fn compare_legal_moves(board: &Board, moves: &[Move]) {
+    let moves: FxHashSet<_> = moves.iter().copied().map(Move::as_lan).collect();
+    let board2: chess::Board = board.as_fen().to_string().parse().unwrap();
+    let moves2: FxHashSet<Lan> = chess::MoveGen::new_legal(&board2).map(Into::into).collect();
+    if let Some(movement) = moves.difference(&moves2).next() {
+        panic!(
+            "found {movement} but it's not a legal move\n{}\n{}",
+            BoardDisplay::new(board),
+            board.as_fen(),
+        );
+    }
+    if let Some(movement) = moves2.difference(&moves).next() {
+        panic!(
+            "{movement} not found\n{}\n{}",
+            BoardDisplay::new(board),
+            board.as_fen(),
+        );
+    }
+}
+fn assert_restored(board: &Board, hashable: HashableBoard, zobrist: u64, movement: Lan) {
+    assert_eq!(
+        board.as_hashable(),
+        hashable,
+        "unmaking {movement} didn't restore the position\n{}",
+        BoardDisplay::new(board),
+    );
+    assert_eq!(
+        board.zobrist(),
+        zobrist,
+        "unmaking {movement} didn't restore the zobrist key\n{}",
+        BoardDisplay::new(board),
+    );
+}
@@ -1,35 +1,51 @@
 use std::{
     cmp::Ordering,
-    collections::HashMap,
-    iter::from_fn,
-    mem::replace,
+    iter::{from_fn, once},
+    mem::{replace, take},
+    slice,
     sync::{
-        LazyLock, RwLock,
-        atomic::{self, AtomicBool},
+        Arc, LazyLock, Mutex,
+        atomic::{self, AtomicBool, AtomicI64, AtomicU64},
         mpsc::{Sender, channel},
     },
-    thread::{Builder, panicking, scope},
+    thread::{Builder, panicking},
 };
 
-use rustc_hash::FxHashMap;
-
 use crate::{
     board::{Board, HashableBoard, Lan},
     color::Color,
-    end_state::EndState,
-    heuristics::Score,
-    misc::{Extended, cold_path},
+    end_state::{EndReason, EndState},
+    heuristics::{Estimated, PawnAdvancement, Score},
+    history::History,
+    misc::{CompoundI8, Extended},
+    move_order::{MoveOrder, is_quiet},
+    thread_pool::execute,
 };
 
 type MoveTreePair = (Lan, Option<Lan>, GameTreeInner);
 
+const NULL_MOVE_REDUCTION: u32 = 2;
+const LATE_MOVE_REDUCTION: u32 = 1;
+// moves searched before reducing the rest
+const FULL_DEPTH_MOVES: usize = 3;
+const MIN_REDUCTION_DEPTH: u32 = 3;
+// with bounded memory, only nodes before this ply keep all of their children
+const RETAINED_PLIES: u32 = 1;
+
 #[derive(Debug, Clone)]
 enum Data {
     Board(Box<Board>),
     Children {
-        board: Box<HashableBoard>,
+        board: Box<Board>,
+        check: bool,
         children: Box<[MoveTreePair]>,
     },
+    // The children were freed to bound memory, only the best one is kept for
+    // the principal variation and to be searched first next time
+    Collapsed {
+        board: Box<Board>,
+        best: Box<MoveTreePair>,
+    },
     End(EndState),
 }
 
@@ -61,39 +77,99 @@ impl GameTreeInner {
         });
         drop(DROPPER.send(self));
     }
+    // Takes the board back from a tree that hasn't ended, the rest is dropped
+    fn into_board(self) -> Box<Board> {
+        match self.data {
+            Data::Board(board) => board,
+            Data::Children {
+                board, children, ..
+            } => {
+                for (_, _, game_tree) in children {
+                    game_tree.drop();
+                }
+                board
+            }
+            Data::Collapsed { board, best } => {
+                best.2.drop();
+                board
+            }
+            Data::End(_) => panic!("the tree has ended"),
+        }
+    }
+    fn to_board(&self) -> Option<Board> {
+        match &self.data {
+            Data::Board(board) | Data::Children { board, .. } | Data::Collapsed { board, .. } => {
+                Some(Board::clone(board))
+            }
+            Data::End(_) => None,
+        }
+    }
     fn board(&self) -> Option<HashableBoard> {
         match &self.data {
-            Data::Board(board) => Some(board.as_hashable()),
-            Data::Children { board, .. } => Some(**board),
+            Data::Board(board) | Data::Children { board, .. } | Data::Collapsed { board, .. } => {
+                Some(board.as_hashable())
+            }
+            Data::End(_) => None,
+        }
+    }
+    fn zobrist(&self) -> Option<u64> {
+        match &self.data {
+            Data::Board(board) | Data::Children { board, .. } | Data::Collapsed { board, .. } => {
+                Some(board.zobrist())
+            }
             Data::End(_) => None,
         }
     }
+    fn key(&self, verification: bool) -> Option<TableKey> {
+        Some(TableKey {
+            zobrist: self.zobrist()?,
+            board: if verification {
+                Some(self.board()?)
+            } else {
+                None
+            },
+        })
+    }
     fn children(&self) -> Option<&[MoveTreePair]> {
-        if let Data::Children { children, .. } = &self.data {
-            Some(children)
-        } else {
-            None
+        match &self.data {
+            Data::Children { children, .. } => Some(children),
+            Data::Collapsed { best, .. } => Some(slice::from_ref(best)),
+            Data::Board(_) | Data::End(_) => None,
         }
     }
     fn children_or_init(&mut self) -> Option<&mut Box<[MoveTreePair]>> {
         match &mut self.data {
-            Data::Board(board) => {
-                let board = Board::clone(board);
-                let hashable = board.as_hashable();
+            Data::Board(_) | Data::Collapsed { .. } => {
+                let dummy = Data::End(EndState::Draw(EndReason::Repetition));
+                let (board, best) = match replace(&mut self.data, dummy) {
+                    Data::Board(board) => (board, None),
+                    Data::Collapsed { board, best } => (board, Some(best)),
+                    Data::Children { .. } | Data::End(_) => unreachable!(),
+                };
+                let mut children: Box<[_]> = board
+                    .valid_moves()
+                    .unwrap()
+                    .map(|movement| {
+                        let (first, second) = movement.as_lan_pair();
+                        (
+                            first,
+                            second,
+                            GameTreeInner::new(board.clone_and_move(movement)),
+                        )
+                    })
+                    .collect();
+                if let Some(best) = best {
+                    let i = children
+                        .iter()
+                        .position(|(first, _, _)| *first == best.0)
+                        .unwrap();
+                    children[i] = *best;
+                    children[..=i].rotate_right(1);
+                }
                 self.data = Data::Children {
-                    board: Box::new(hashable),
-                    children: board
-                        .valid_moves()
-                        .unwrap()
-                        .map(|movement| {
-                            let (first, second) = movement.as_lan_pair(&board);
-                            (
-                                first,
-                                second,
-                                GameTreeInner::new(board.clone_and_move(movement)),
-                            )
-                        })
-                        .collect(),
+                    check: board.is_check(),
+                    board,
+                    children,
                 };
             }
             Data::Children { .. } => (),
@@ -106,142 +182,337 @@ impl GameTreeInner {
     }
     fn current_player(&self) -> Option<Color> {
         match &self.data {
-            Data::Board(board) => Some(board.current_player()),
-            Data::Children { board, .. } => Some(board.current_player),
+            Data::Board(board) | Data::Children { board, .. } | Data::Collapsed { board, .. } => {
+                Some(board.current_player())
+            }
             Data::End(_) => None,
         }
     }
-    fn search_children(&mut self, setting: SearchSetting) -> u32 {
-        let mut nodes = 1;
+    fn is_check(&self) -> bool {
+        match &self.data {
+            Data::Board(board) | Data::Collapsed { board, .. } => board.is_check(),
+            Data::Children { check, .. } => *check,
+            Data::End(_) => false,
+        }
+    }
+    // Lets the opponent move twice in a row, if the current player is still
+    // doing well enough for a cutoff, the real moves are assumed to be as well.
+    // A mate can't be proven this way, so mate bounds are left alone.
+    fn null_move(&mut self, setting: SearchSetting) -> (Option<Score>, u32) {
+        if !setting.options.null_move_pruning
+            || setting.after_null_move
+            || setting.ply == 0
+            || setting.depth <= NULL_MOVE_REDUCTION
+        {
+            return (None, 0);
+        }
         let current_player = self.current_player().unwrap();
-        let children = self.children_or_init().unwrap();
+        let alpha_beta = AlphaBetaState::new(current_player, setting);
+        let Some((window, bound @ Score::Estimated(_))) = alpha_beta.null_window() else {
+            return (None, 0);
+        };
+        if self.is_check() {
+            return (None, 0);
+        }
+        // the node's own board is passed and searched, then handed back
+        let dummy = Data::End(EndState::Draw(EndReason::Repetition));
+        let (mut board, children, best) = match replace(&mut self.data, dummy) {
+            Data::Board(board) => (board, None, None),
+            Data::Children {
+                board,
+                check,
+                children,
+            } => (board, Some((check, children)), None),
+            Data::Collapsed { board, best } => (board, None, Some(best)),
+            Data::End(_) => unreachable!(),
+        };
+        let undo = board.pass();
+        // a pass that ends the game isn't worth searching
+        let (score, nodes) =
+            if board.has_non_pawn_material(current_player) && board.end_state().is_none() {
+                let mut game_tree = GameTreeInner {
+                    data: Data::Board(board),
+                    score: None,
+                };
+                let nodes = game_tree.search(setting.null_move(window));
+                let score = game_tree.score.map(|score| score.to_root(setting.ply + 1));
+                board = game_tree.into_board();
+                (score, nodes)
+            } else {
+                (None, 0)
+            };
+        board.unpass(undo);
+        self.data = match (children, best) {
+            (Some((check, children)), _) => Data::Children {
+                board,
+                check,
+                children,
+            },
+            (None, Some(best)) => Data::Collapsed { board, best },
+            (None, None) => Data::Board(board),
+        };
+        let cutoff = score.is_some_and(|score| window.improves(score));
+        if cutoff && !setting.is_stopped() {
+            (Some(bound), nodes)
+        } else {
+            (None, nodes)
+        }
+    }
+    fn search_children(&mut self, setting: SearchSetting, table_move: Option<Lan>) -> u32 {
+        let (null_move_score, mut nodes) = self.null_move(setting);
+        nodes += 1;
+        if let Some(score) = null_move_score {
+            self.score = Some(score.to_position(setting.ply));
+            return nodes;
+        }
+        let current_player = self.current_player().unwrap();
+        let check = self.is_check();
+        let board = self.board().unwrap();
+        let is_late = |i, movement| i >= FULL_DEPTH_MOVES && !check && is_quiet(&board, movement);
+        // the child kept by a collapsed node is already first
+        let kept = usize::from(matches!(self.data, Data::Collapsed { .. }));
+        let mut children: &mut [MoveTreePair] = self.children_or_init().unwrap();
+        order_fresh_children(&mut children[kept..], &board, setting, table_move);
+        if setting.ply == 0
+            && let Some(search_moves) = setting.search_moves
+        {
+            children = restrict_children(children, search_moves);
+        }
         let mut alpha_beta = AlphaBetaState::new(current_player, setting);
 
         let mut searched_children = 0;
+        let ply = setting.ply + 1;
 
-        if setting.multithread_depth == Some(0) {
-            for chunk in children.chunks_mut(setting.thread_count) {
-                searched_children += chunk.len();
-                let stop = scope(|scope| {
-                    let handles: Box<[_]> = chunk
-                        .iter_mut()
-                        .map(|(_, _, game_tree)| {
-                            scope.spawn(move || {
-                                let nodes = game_tree.search(setting.deeper(alpha_beta));
-                                (nodes, game_tree.score)
-                            })
-                        })
-                        .collect();
-                    let mut stop = false;
-                    for handle in handles {
-                        let (additional_nodes, score) = handle.join().unwrap();
-                        nodes += additional_nodes;
-                        if let Some(score) = score
-                            && alpha_beta.set(score)
-                        {
-                            stop = true;
-                        }
-                    }
-                    stop
-                });
-                if stop {
-                    break;
-                }
+        // A child whose search got interrupted by stopping is left out and gets
+        // its previous score back
+        while searched_children < children.len() {
+            let window = alpha_beta.multi_pv_window(setting, &children[..searched_children]);
+            let (movement, _, game_tree) = &mut children[searched_children];
+            let movement = *movement;
+            let late = is_late(searched_children, movement);
+            let previous_score = game_tree.score;
+            nodes += game_tree.search_child(setting, window, searched_children == 0, late);
+            if setting.is_stopped() {
+                game_tree.score = previous_score;
+                break;
             }
-        } else {
-            for (_, _, game_tree) in &mut *children {
-                nodes += game_tree.search(setting.deeper(alpha_beta));
-                searched_children += 1;
-                if let Some(score) = game_tree.score
-                    && alpha_beta.set(score)
-                {
-                    break;
-                }
+            searched_children += 1;
+            if let Some(score) = game_tree.score.map(|score| score.to_root(ply))
+                && alpha_beta.set(score)
+            {
+                setting.record_cutoff(&board, movement);
+                break;
             }
         }
-        children[..searched_children].sort_unstable_by(|(_, _, a), (_, _, b)| {
-            let ord = match (a.score, b.score) {
-                (None, None) => Ordering::Equal,
-                (None, Some(_)) => Ordering::Less,
-                (Some(_), None) => Ordering::Greater,
-                (Some(a), Some(b)) => match current_player {
-                    Color::White => Ord::cmp(&a, &b),
-                    Color::Black => Ord::cmp(&a, &b).reverse(),
-                },
-            };
-            ord.reverse()
-        });
-        self.score = alpha_beta.score.into_finite();
+        sort_children(&mut children[..searched_children], current_player);
+        self.score = alpha_beta
+            .score
+            .into_finite()
+            .map(|score| score.to_position(setting.ply));
         nodes
     }
-    fn search(&mut self, setting: SearchSetting) -> u32 {
-        if setting
-            .stop_signal
-            .is_some_and(|signal| signal.load(atomic::Ordering::Relaxed))
+    // Searches the position as a child of the node searched with `setting`.
+    // Checking moves are extended. Children after the first are expected to be
+    // worse and are only tested with a null window, late quiet moves are
+    // tested with reduced depth as well. They're only searched again in full
+    // when they turn out better than expected.
+    fn search_child(
+        &mut self,
+        setting: SearchSetting,
+        window: AlphaBetaState,
+        first: bool,
+        late: bool,
+    ) -> u32 {
+        let check = self.is_check();
+        let mut child_setting = setting.deeper(window);
+
+        // past twice the root depth, perpetual checks could extend forever
+        if check && setting.options.check_extensions && setting.ply < 2 * setting.root_depth {
+            child_setting.depth += 1;
+        }
+        let null_window = window
+            .pvs_window()
+            .filter(|_| !first && setting.options.principal_variation_search);
+        let test_window = null_window.unwrap_or(window);
+        let score = |game_tree: &Self| game_tree.score.map(|score| score.to_root(setting.ply + 1));
+        let mut nodes = 0;
+        if late
+            && !check
+            && setting.options.late_move_reductions
+            && setting.ply != 0
+            && setting.depth >= MIN_REDUCTION_DEPTH
         {
+            nodes += self.search(
+                child_setting
+                    .with_window(test_window)
+                    .reduced(LATE_MOVE_REDUCTION),
+            );
+            if setting.is_stopped() || !score(self).is_some_and(|score| window.improves(score)) {
+                return nodes;
+            }
+        }
+        if let Some(null_window) = null_window
+            && null_window != window
+        {
+            nodes += self.search(child_setting.with_window(null_window));
+            if setting.is_stopped() || !score(self).is_some_and(|score| window.is_within(score)) {
+                return nodes;
+            }
+        }
+        nodes + self.search(child_setting)
+    }
+    fn search(&mut self, setting: SearchSetting) -> u32 {
+        if !setting.visit() {
             // Do nothing
-            1
+            0
         } else if let Data::End(end_state) = self.data {
             self.score = Some(Score::from_end_state(end_state));
             1
         } else {
-            let board = self.board().unwrap();
-
-            let read = setting.table.read().unwrap();
-
-            if let Some(table) = read.get(&board) {
-                if table.repetition {
-                    return 1;
-                } else if let Some(score) = table.transposition {
-                    self.score = Some(score);
-                    return 1;
-                }
+            // positions that already happened in the game or earlier in the
+            // search are considered as draws, the root is exempted since it is
+            // the current position
+            let zobrist = self.zobrist().unwrap();
+            if setting.ply != 0
+                && (setting.history.contains(zobrist)
+                    || setting.path.is_some_and(|path| path.contains(zobrist)))
+            {
+                self.score = Some(Score::from_end_state(EndState::Draw(EndReason::Repetition)));
+                return 1;
             }
-            drop(read);
-            if setting.depth == 0 {
-                let score = self.estimate();
+            let key = self.key(setting.table.verification()).unwrap();
+            let entry = setting.table.get(&key);
+
+            // the root is always searched so its children get scores, even
+            // when another thread has already stored it
+            if setting.ply != 0
+                && let Some(score) = entry.and_then(|entry| entry.probe(setting))
+            {
                 self.score = Some(score);
-                let mut write = setting.table.write().unwrap();
-                write.insert_transposition(board, score);
-                drop(write);
-                1
+                return 1;
+            }
+            let nodes = if setting.depth == 0 {
+                let (score, nodes) = match &mut self.data {
+                    Data::Board(board)
+                    | Data::Children { board, .. }
+                    | Data::Collapsed { board, .. } => quiescence(board, setting),
+                    Data::End(_) => unreachable!(),
+                };
+                self.score = score;
+                nodes
             } else {
-                let mut write = setting.table.write().unwrap();
-                write.insert_repetition(board);
-                drop(write);
-                let nodes = self.search_children(setting);
-                let mut write = setting.table.write().unwrap();
-                let table_value = write.get_mut(&board).unwrap();
-                table_value.repetition = false;
-                if let Some(score) = self.score {
-                    table_value.transposition = Some(score);
+                let path = Path {
+                    zobrist,
+                    parent: setting.path,
+                };
+                let nodes = self.search_children(
+                    SearchSetting {
+                        path: Some(&path),
+                        ..setting
+                    },
+                    entry.and_then(|entry| entry.best_move),
+                );
+                if setting.options.bounded_memory && setting.ply >= RETAINED_PLIES {
+                    self.collapse();
                 }
-                drop(write);
                 nodes
+            };
+            if let Some(score) = self.score
+                && !setting.is_stopped()
+            {
+                let best_move = self
+                    .best_move_tree_pair()
+                    .filter(|(_, _, game_tree)| game_tree.score.is_some())
+                    .map(|(movement, _, _)| *movement);
+                setting
+                    .table
+                    .insert(key, TableValue::new(score, setting, best_move));
             }
+            nodes
         }
     }
-    fn estimate(&self) -> Score {
-        let estimated = if let Some(score) = self.score {
-            return score;
-        } else if let Data::Board(board) = &self.data {
-            board.estimate()
-        } else if cfg!(debug_assertions) {
-            panic!("missing score");
-        } else {
-            // last resort
-
-            cold_path();
-            match &self.data {
-                Data::Board(_) => unreachable!(),
-                Data::Children { board, .. } => {
-                    let board: Board = (**board).try_into().unwrap();
-                    board.estimate()
+    // Frees the children except the best one, which keeps its own line
+    fn collapse(&mut self) {
+        if !matches!(self.data, Data::Children { .. }) {
+            return;
+        }
+        let dummy = Data::End(EndState::Draw(EndReason::Repetition));
+        let Data::Children {
+            board, children, ..
+        } = replace(&mut self.data, dummy)
+        else {
+            unreachable!()
+        };
+        let best = children
+            .into_iter()
+            .next()
+            .filter(|(_, _, game_tree)| game_tree.score.is_some());
+        self.data = match best {
+            Some(best) => Data::Collapsed {
+                board,
+                best: Box::new(best),
+            },
+            None => Data::Board(board),
+        };
+    }
+    // Searches deeper and deeper until stopped, the main thread benefits from
+    // the scores it leaves in the shared table
+    fn help(&mut self, depth: u32, shared: &Shared) -> u32 {
+        let mut nodes = 0;
+        for depth in depth.. {
+            let setting = shared.setting(depth);
+            nodes += self.search(setting);
+            if setting.is_stopped() {
+                break;
+            }
+        }
+        nodes
+    }
+    fn move_piece(&mut self, movement: Lan) {
+        let new = match &mut self.data {
+            Data::Board(_) => {
+                let dummy = Data::End(EndState::Draw(EndReason::Repetition));
+                let data = replace(&mut self.data, dummy);
+                let Data::Board(board) = data else {
+                    unreachable!()
+                };
+                let mut board = *board;
+                board.move_lan(movement);
+                GameTreeInner::new(board)
+            }
+            Data::Children { children, .. } => {
+                let i = children
+                    .iter()
+                    .position(|(first, second, _)| movement == *first || Some(movement) == *second)
+                    .unwrap();
+                let (first, second, _) = &children[i];
+                let dummy = (
+                    *first,
+                    *second,
+                    GameTreeInner {
+                        data: Data::End(EndState::Draw(EndReason::Repetition)),
+                        score: None,
+                    },
+                );
+                let (_, _, game_tree) = replace(&mut children[i], dummy);
+                game_tree
+            }
+            Data::Collapsed { board, best } => {
+                if movement == best.0 || Some(movement) == best.1 {
+                    let dummy = GameTreeInner {
+                        data: Data::End(EndState::Draw(EndReason::Repetition)),
+                        score: None,
+                    };
+                    replace(&mut best.2, dummy)
+                } else {
+                    let mut board = Board::clone(board);
+                    board.move_lan(movement);
+                    GameTreeInner::new(board)
                 }
-                Data::End(end_state) => return Score::from_end_state(*end_state),
             }
+            Data::End(_) => panic!("cannot move on end state"),
         };
-        Score::Estimated(estimated)
+        replace(self, new).drop();
     }
     fn generate(&mut self, depth: u32) -> u32 {
         let mut nodes = 1;
@@ -257,196 +528,855 @@ impl GameTreeInner {
     fn best_move_tree_pair(&self) -> Option<&MoveTreePair> {
         self.children().map(|children| &children[0])
     }
+    fn best_line(&self) -> impl Iterator<Item = Lan> {
+        let mut game_tree = self;
+        from_fn(move || {
+            game_tree
+                .best_move_tree_pair()
+                .map(|(movement, _, new_game_tree)| {
+                    game_tree = new_game_tree;
+                    *movement
+                })
+        })
+    }
+}
+// The adjacent scores as bounds of a window
+fn next(score: Score) -> Extended<Score> {
+    score.next().map_or(Extended::Inf, Extended::Finite)
+}
+fn prev(score: Score) -> Extended<Score> {
+    score.prev().map_or(Extended::NegInf, Extended::Finite)
+}
+// Best first according to the scores of the previous search
+fn sort_children(children: &mut [MoveTreePair], current_player: Color) {
+    children.sort_unstable_by(|(_, _, a), (_, _, b)| {
+        let ord = match (a.score, b.score) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a), Some(b)) => match current_player {
+                Color::White => Ord::cmp(&a, &b),
+                Color::Black => Ord::cmp(&a, &b).reverse(),
+            },
+        };
+        ord.reverse()
+    });
+}
+// Nodes searched for the first time have no scores to sort by yet, the best
+// move found by a previous search of the same position goes first
+fn order_fresh_children(
+    children: &mut [MoveTreePair],
+    board: &HashableBoard,
+    setting: SearchSetting,
+    table_move: Option<Lan>,
+) {
+    if setting.options.move_ordering
+        && children
+            .iter()
+            .all(|(_, _, game_tree)| game_tree.score.is_none())
+    {
+        children.sort_by_cached_key(|(movement, _, _)| {
+            (
+                Some(*movement) != table_move,
+                setting.move_order.sort_key(board, *movement, setting.ply),
+            )
+        });
+    }
+}
+// Moves that aren't searched are kept for the next search, but unscored and
+// placed last. When none of the moves are legal, every move is searched.
+fn restrict_children<'a>(
+    children: &'a mut [MoveTreePair],
+    search_moves: &[Lan],
+) -> &'a mut [MoveTreePair] {
+    let is_searched = |(first, second, _): &MoveTreePair| {
+        search_moves
+            .iter()
+            .any(|movement| movement == first || Some(movement) == second.as_ref())
+    };
+    if !children.iter().any(is_searched) {
+        return children;
+    }
+    children.sort_by_key(|pair| !is_searched(pair));
+    let len = children.iter().take_while(|pair| is_searched(pair)).count();
+    let (searched, rest) = children.split_at_mut(len);
+    for (_, _, game_tree) in rest {
+        game_tree.score = None;
+    }
+    searched
+}
+// Resolves captures, promotions, and check evasions so the position is only
+// estimated when it's quiet. The position itself should already be counted
+// with `SearchSetting::visit`.
+fn quiescence(board: &mut Board, setting: SearchSetting) -> (Option<Score>, u32) {
+    if setting.is_stopped() {
+        return (None, 1);
+    }
+    let moves = match board.valid_moves() {
+        Ok(moves) => moves,
+        Err(end_state) => return (Some(Score::from_end_state(end_state)), 1),
+    };
+    let mut alpha_beta = AlphaBetaState::new(board.current_player(), setting);
+    let check = board.is_check();
+
+    // when not in check, the current player may choose to not capture
+    if !check && alpha_beta.set(Score::Estimated(board.estimate())) {
+        return (alpha_beta.score.into_finite(), 1);
+    }
+    let mut nodes = 1;
+    for movement in
+        moves.filter(|movement| check || movement.is_capture() || movement.is_promotion())
+    {
+        if !setting.visit() {
+            break;
+        }
+        let undo = board.make(movement);
+        let (score, additional_nodes) = quiescence(board, setting.quiescence_deeper(alpha_beta));
+        board.unmake(undo);
+        nodes += additional_nodes;
+        if let Some(score) = score.map(|score| score.to_root(setting.ply + 1))
+            && alpha_beta.set(score)
+        {
+            break;
+        }
+    }
+    let score = alpha_beta
+        .score
+        .into_finite()
+        .map(|score| score.to_position(setting.ply));
+    (score, nodes)
+}
+// The positions from the root to the node being searched, linked through the
+// stack frames of the search
+#[derive(Debug, Clone, Copy)]
+struct Path<'a> {
+    zobrist: u64,
+    parent: Option<&'a Path<'a>>,
+}
+impl Path<'_> {
+    fn contains(&self, zobrist: u64) -> bool {
+        let mut path = Some(self);
+        while let Some(current) = path {
+            if current.zobrist == zobrist {
+                return true;
+            }
+            path = current.parent;
+        }
+        false
+    }
 }
 #[derive(Debug, Clone, Copy)]
-struct SearchSetting<'lock, 'table, 'bool> {
+struct SearchSetting<'table, 'bool, 'history, 'moves, 'path> {
     depth: u32,
+    ply: u32,
     alpha: Extended<Score>,
     beta: Extended<Score>,
-    table: &'lock RwLock<&'table mut Table>,
-    multithread_depth: Option<u32>,
-    thread_count: usize,
+    table: &'table Table,
+    history: &'history History,
+    move_order: &'history MoveOrder,
+    path: Option<&'path Path<'path>>,
+    // only used at the root
+    multi_pv: usize,
+    search_moves: Option<&'moves [Lan]>,
     stop_signal: Option<&'bool AtomicBool>,
+    // shared between threads, the search stops once it's used up
+    node_budget: Option<&'bool AtomicI64>,
+    root_depth: u32,
+    options: SearchOptions,
+    after_null_move: bool,
 }
-impl SearchSetting<'_, '_, '_> {
+impl SearchSetting<'_, '_, '_, '_, '_> {
+    fn is_stopped(self) -> bool {
+        self.stop_signal
+            .is_some_and(|signal| signal.load(atomic::Ordering::Relaxed))
+            || self
+                .node_budget
+                .is_some_and(|budget| budget.load(atomic::Ordering::Relaxed) <= 0)
+    }
+    // Counts a node against the budget, returns false if the search should stop
+    fn visit(self) -> bool {
+        !self.is_stopped()
+            && self
+                .node_budget
+                .is_none_or(|budget| budget.fetch_sub(1, atomic::Ordering::Relaxed) > 0)
+    }
+    fn record_cutoff(self, board: &HashableBoard, movement: Lan) {
+        if self.options.move_ordering {
+            self.move_order
+                .record_cutoff(board, movement, self.ply, self.depth);
+        }
+    }
     fn deeper(self, alpha_beta: AlphaBetaState) -> Self {
         SearchSetting {
             depth: self.depth - 1,
+            ply: self.ply + 1,
+            alpha: alpha_beta.alpha,
+            beta: alpha_beta.beta,
+            after_null_move: false,
+            ..self
+        }
+    }
+    fn null_move(self, alpha_beta: AlphaBetaState) -> Self {
+        let setting = self.deeper(alpha_beta);
+        SearchSetting {
+            depth: setting.depth - NULL_MOVE_REDUCTION,
+            after_null_move: true,
+            ..setting
+        }
+    }
+    fn reduced(self, reduction: u32) -> Self {
+        SearchSetting {
+            depth: self.depth - reduction,
+            ..self
+        }
+    }
+    fn with_window(self, alpha_beta: AlphaBetaState) -> Self {
+        SearchSetting {
+            alpha: alpha_beta.alpha,
+            beta: alpha_beta.beta,
+            ..self
+        }
+    }
+    fn quiescence_deeper(self, alpha_beta: AlphaBetaState) -> Self {
+        SearchSetting {
+            ply: self.ply + 1,
             alpha: alpha_beta.alpha,
             beta: alpha_beta.beta,
-            multithread_depth: self
-                .multithread_depth
-                .and_then(|depth| depth.checked_sub(1)),
             ..self
         }
     }
 }
+// What helper threads share with the main thread, they own it since they
+// outlive the call that started them
 #[derive(Debug, Clone)]
-pub struct GameTree(GameTreeInner);
-
+struct Shared {
+    table: Arc<Table>,
+    history: Arc<History>,
+    move_order: Arc<MoveOrder>,
+    multi_pv: usize,
+    search_moves: Option<Arc<[Lan]>>,
+    stop_signal: Arc<AtomicBool>,
+    node_budget: Option<Arc<AtomicI64>>,
+    options: SearchOptions,
+}
+impl Shared {
+    fn setting(&self, depth: u32) -> SearchSetting<'_, '_, '_, '_, '_> {
+        SearchSetting {
+            depth,
+            ply: 0,
+            alpha: Extended::NegInf,
+            beta: Extended::Inf,
+            table: &self.table,
+            history: &self.history,
+            move_order: &self.move_order,
+            path: None,
+            multi_pv: self.multi_pv,
+            search_moves: self.search_moves.as_deref(),
+            stop_signal: Some(&self.stop_signal),
+            node_budget: self.node_budget.as_deref(),
+            root_depth: depth,
+            options: self.options,
+            after_null_move: false,
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "each is an independent toggle for comparing node counts"
+)]
+pub struct SearchOptions {
+    pub null_move_pruning: bool,
+    pub late_move_reductions: bool,
+    pub check_extensions: bool,
+    pub move_ordering: bool,
+    pub principal_variation_search: bool,
+    pub aspiration_windows: bool,
+    // frees searched nodes past the root except for their best lines, so
+    // memory doesn't grow with search time
+    pub bounded_memory: bool,
+}
+impl SearchOptions {
+    pub const UNPRUNED: Self = SearchOptions {
+        null_move_pruning: false,
+        late_move_reductions: false,
+        check_extensions: false,
+        move_ordering: false,
+        principal_variation_search: false,
+        aspiration_windows: false,
+        bounded_memory: false,
+    };
+}
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            null_move_pruning: true,
+            late_move_reductions: true,
+            check_extensions: true,
+            move_ordering: true,
+            principal_variation_search: true,
+            aspiration_windows: true,
+            bounded_memory: false,
+        }
+    }
+}
+#[derive(Debug)]
+pub struct GameTree {
+    root: GameTreeInner,
+    history: Arc<History>,
+    move_order: Arc<MoveOrder>,
+    options: SearchOptions,
+    // How the root score relates to the true score, it's only a bound when a
+    // search is stopped before widening the window the score fell outside of
+    bound: Bound,
+    // the number of best lines searched with exact windows
+    exact_lines: usize,
+}
 impl GameTree {
     pub fn new(board: Board) -> Self {
-        GameTree(GameTreeInner::new(board))
+        GameTree::with_history(board, History::new())
+    }
+    pub fn with_history(board: Board, history: History) -> Self {
+        GameTree {
+            root: GameTreeInner::new(board),
+            history: Arc::new(history),
+            move_order: Arc::new(MoveOrder::new()),
+            options: SearchOptions::default(),
+            bound: Bound::Exact,
+            exact_lines: 1,
+        }
+    }
+    pub fn set_options(&mut self, options: SearchOptions) {
+        self.options = options;
     }
     pub fn move_piece(&mut self, movement: Lan) {
-        let new = match &mut self.0.data {
-            Data::Board(_) => {
-                let dummy = Data::End(EndState::Draw);
-                let data = replace(&mut self.0.data, dummy);
-                let Data::Board(board) = data else {
-                    unreachable!()
-                };
-                let mut board = *board;
-                board.move_lan(movement);
-                GameTreeInner::new(board)
-            }
-            Data::Children { children, .. } => {
-                let i = children
-                    .iter()
-                    .position(|(first, second, _)| movement == *first || Some(movement) == *second)
-                    .unwrap();
-                let (first, second, _) = &children[i];
-                let dummy = (
-                    *first,
-                    *second,
-                    GameTreeInner {
-                        data: Data::End(EndState::Draw),
-                        score: None,
-                    },
-                );
-                let (_, _, game_tree) = replace(&mut children[i], dummy);
-                game_tree
-            }
-            Data::End(_) => panic!("cannot move on end state"),
-        };
-        replace(&mut self.0, new).drop();
+        if let Some(zobrist) = self.root.zobrist() {
+            Arc::make_mut(&mut self.history).push(zobrist);
+        }
+        self.move_order.age();
+        let index = self.root.children().and_then(|children| {
+            children
+                .iter()
+                .position(|(first, second, _)| movement == *first || Some(movement) == *second)
+        });
+        self.bound = index.map_or(Bound::Exact, |index| self.line_bound(index));
+        self.exact_lines = 1;
+        self.root.move_piece(movement);
     }
+    // Lazy SMP: helper threads search the same position independently, half
+    // of them one ply deeper, while the main thread searches as usual. They
+    // only cooperate through the shared table. Helper trees are fresh for
+    // every search and freed right after, so they don't hold memory between
+    // searches.
+    #[allow(clippy::too_many_arguments)]
     fn calculate_raw(
         &mut self,
         depth: u32,
         table: &mut Table,
         thread_count: usize,
+        multi_pv: usize,
+        search_moves: Option<&[Lan]>,
         stop_signal: Option<&AtomicBool>,
+        node_budget: Option<&Arc<AtomicI64>>,
     ) -> u32 {
-        table.clear();
-        let multithread_depth = if thread_count > 1 {
-            Some(depth / 2)
-        } else {
-            None
+        let shared = Shared {
+            table: Arc::new(take(table)),
+            history: Arc::clone(&self.history),
+            move_order: Arc::clone(&self.move_order),
+            multi_pv,
+            search_moves: search_moves.map(Arc::from),
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            node_budget: node_budget.cloned(),
+            options: self.options,
         };
-        self.0.search(SearchSetting {
-            depth,
-            alpha: Extended::NegInf,
-            beta: Extended::Inf,
-            table: &RwLock::new(table),
-            multithread_depth,
-            thread_count,
-            stop_signal,
-        })
+        // no helpers are needed when the game has ended
+        let receivers: Vec<_> = match self.root.to_board() {
+            Some(board) => (0..thread_count.saturating_sub(1))
+                .zip([0, 1].into_iter().cycle())
+                .map(|(i, extra_depth)| {
+                    let shared = shared.clone();
+                    let board = board.clone();
+                    execute(i, move || {
+                        let mut helper = GameTreeInner::new(board);
+                        let nodes = helper.help(depth + extra_depth, &shared);
+                        helper.drop();
+                        nodes
+                    })
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let (mut alpha, mut beta) = self.aspiration_window(multi_pv);
+        let mut nodes = 0;
+        // the score of a search failing outside of the window and the bound
+        // it found, until the widened search finishes
+        let mut failed = None;
+        loop {
+            let setting = SearchSetting {
+                depth,
+                ply: 0,
+                alpha,
+                beta,
+                table: &shared.table,
+                history: &self.history,
+                move_order: &self.move_order,
+                path: None,
+                multi_pv,
+                search_moves,
+                stop_signal,
+                node_budget: node_budget.map(|budget| &**budget),
+                root_depth: depth,
+                options: self.options,
+                after_null_move: false,
+            };
+            nodes += self.root.search(setting);
+            if setting.is_stopped() {
+                break;
+            }
+            match self.root.score {
+                Some(score) if Extended::Finite(score) <= alpha => {
+                    alpha = Extended::NegInf;
+                    failed = Some((score, Bound::Upper));
+                }
+                Some(score) if Extended::Finite(score) >= beta => {
+                    beta = Extended::Inf;
+                    failed = Some((score, Bound::Lower));
+                }
+                _ => {
+                    failed = None;
+                    break;
+                }
+            }
+        }
+        // a stopped search only has a bound when it fails outside of the
+        // window or gets stopped before the widened search finishes
+        self.bound = match (failed, self.root.score.map(Extended::Finite)) {
+            (Some((_, bound)), Some(_)) => bound,
+            (Some((score, bound)), None) => {
+                self.root.score = Some(score);
+                bound
+            }
+            (None, Some(score)) if score >= beta => Bound::Lower,
+            (None, Some(score)) if score <= alpha => Bound::Upper,
+            (None, _) => Bound::Exact,
+        };
+        self.exact_lines = multi_pv;
+        shared.stop_signal.store(true, atomic::Ordering::Relaxed);
+        for receiver in receivers {
+            nodes += receiver.recv().unwrap();
+        }
+        let Shared { table: shared, .. } = shared;
+        *table = Arc::into_inner(shared).unwrap();
+        nodes
+    }
+    // A window around the score of the previous search, the side it fails on
+    // gets widened
+    fn aspiration_window(&self, multi_pv: usize) -> (Extended<Score>, Extended<Score>) {
+        match self.root.score {
+            Some(Score::Estimated(estimated))
+                if self.options.aspiration_windows && multi_pv <= 1 =>
+            {
+                let margin = Estimated {
+                    material: 1,
+                    ..Estimated::default()
+                };
+                (
+                    Extended::Finite(Score::Estimated(estimated - margin)),
+                    Extended::Finite(Score::Estimated(estimated + margin)),
+                )
+            }
+            _ => (Extended::NegInf, Extended::Inf),
+        }
     }
     pub fn calculate(&mut self, depth: u32, table: &mut Table, thread_count: usize) -> u32 {
-        self.calculate_raw(depth, table, thread_count, None)
+        self.calculate_raw(depth, table, thread_count, 1, None, None, None)
     }
+    #[allow(clippy::too_many_arguments)]
     pub fn calculate_with_stop_signal(
         &mut self,
         depth: u32,
         table: &mut Table,
         stop_signal: &AtomicBool,
+        node_budget: Option<&Arc<AtomicI64>>,
         thread_count: usize,
+        multi_pv: usize,
+        search_moves: Option<&[Lan]>,
     ) -> u32 {
-        self.calculate_raw(depth, table, thread_count, Some(stop_signal))
+        self.calculate_raw(
+            depth,
+            table,
+            thread_count,
+            multi_pv,
+            search_moves,
+            Some(stop_signal),
+            node_budget,
+        )
     }
     pub fn best_move(&self) -> Option<Lan> {
-        self.0
+        self.root
             .best_move_tree_pair()
             .map(|(movement, _, _)| *movement)
     }
     pub fn score(&self) -> Option<Score> {
-        self.0.score
+        self.root.score
+    }
+    pub fn bound(&self) -> Bound {
+        self.bound
+    }
+    // Lines past the exact ones only had to be proven worse than them
+    fn line_bound(&self, index: usize) -> Bound {
+        if index == 0 {
+            self.bound
+        } else if index < self.exact_lines {
+            Bound::Exact
+        } else {
+            match self.root.current_player() {
+                Some(Color::White) => Bound::Upper,
+                Some(Color::Black) => Bound::Lower,
+                None => Bound::Exact,
+            }
+        }
+    }
+    pub fn current_player(&self) -> Option<Color> {
+        self.root.current_player()
     }
     pub fn best_line(&self) -> impl Iterator<Item = Lan> {
-        let mut game_tree = &self.0;
-        from_fn(move || {
-            game_tree
-                .best_move_tree_pair()
-                .map(|(movement, _, new_game_tree)| {
-                    game_tree = new_game_tree;
-                    *movement
-                })
-        })
+        self.root.best_line()
+    }
+    // Every scored move from the root paired with its score, how the score is
+    // bounded, and its line, best first
+    pub fn lines(&self) -> impl Iterator<Item = (Score, Bound, impl Iterator<Item = Lan>)> {
+        self.root
+            .children()
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .filter_map(|(i, (movement, _, game_tree))| {
+                Some((
+                    game_tree.score?.to_root(1),
+                    self.line_bound(i),
+                    once(*movement).chain(game_tree.best_line()),
+                ))
+            })
     }
     pub fn generate(&mut self, depth: u32) -> u32 {
-        self.0.generate(depth)
+        self.root.generate(depth)
     }
 }
 impl Drop for GameTree {
     fn drop(&mut self) {
         if !panicking() {
             let dummy = GameTreeInner {
-                data: Data::End(EndState::Draw),
+                data: Data::End(EndState::Draw(EndReason::Repetition)),
                 score: None,
             };
-            replace(&mut self.0, dummy).drop();
+            replace(&mut self.root, dummy).drop();
         }
     }
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct TableKey {
+    zobrist: u64,
+    // only present when the table verifies entries against the full board
+    board: Option<HashableBoard>,
+}
+// How the stored score relates to the true score, searches failing outside
+// their window only find bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bound {
+    Exact,
+    // the true score is at least the stored score
+    Lower,
+    // the true score is at most the stored score
+    Upper,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct TableValue {
-    transposition: Option<Score>,
-    repetition: bool,
+    // relative to the position, like the scores of `GameTreeInner`
+    score: Score,
+    depth: u32,
+    bound: Bound,
+    best_move: Option<Lan>,
+    age: u8,
 }
-#[derive(Debug, Clone, Default)]
-pub struct Table {
-    table: FxHashMap<HashableBoard, TableValue>,
-    max_capacity: usize,
+impl TableValue {
+    fn new(score: Score, setting: SearchSetting, best_move: Option<Lan>) -> Self {
+        let root_score = Extended::Finite(score.to_root(setting.ply));
+        let bound = if root_score >= setting.beta {
+            Bound::Lower
+        } else if root_score <= setting.alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        TableValue {
+            score,
+            depth: setting.depth,
+            bound,
+            best_move,
+            age: 0,
+        }
+    }
+    // The score if it's from a search at least as deep and settles the
+    // position for the window of `setting`
+    fn probe(self, setting: SearchSetting) -> Option<Score> {
+        let root_score = Extended::Finite(self.score.to_root(setting.ply));
+        let settled = match self.bound {
+            Bound::Exact => true,
+            Bound::Lower => root_score >= setting.beta,
+            Bound::Upper => root_score <= setting.alpha,
+        };
+        (self.depth >= setting.depth && settled).then_some(self.score)
+    }
 }
-impl Table {
-    pub const ELEMENT_SIZE: usize = size_of::<(HashableBoard, TableValue)>();
+// Bit layout from the lowest bit: score (81 bits), bound (2), depth (8), best
+// move (16), age (8), then a bit set on every stored value
+const BOUND_SHIFT: u32 = 81;
+const DEPTH_SHIFT: u32 = 83;
+const MOVE_SHIFT: u32 = 91;
+const AGE_SHIFT: u32 = 107;
+const PRESENT_SHIFT: u32 = 115;
 
-    pub fn new(max_capacity: usize) -> Self {
-        Table {
-            table: HashMap::default(),
-            max_capacity: Ord::min(max_capacity, <i32>::MAX as usize),
+fn pack_score(score: Score) -> u128 {
+    match score {
+        Score::Win(color, plies) => (1 << 80) | (u128::from(color as u8) << 32) | u128::from(plies),
+        Score::Estimated(estimated) => {
+            let pawn_advancement = estimated
+                .pawn_advancement
+                .0
+                .iter()
+                .rev()
+                .fold(0, |bits, value| (bits << 8) | u128::from(value.to_bits()));
+            u128::from(estimated.material.cast_unsigned())
+                | (u128::from(estimated.king_safety.cast_unsigned()) << 16)
+                | (u128::from(estimated.square_control.cast_unsigned()) << 32)
+                | (pawn_advancement << 48)
         }
     }
-    pub fn capacity(&self) -> usize {
-        self.table.capacity()
+}
+#[allow(
+    clippy::cast_possible_truncation,
+    reason = "casting only keeps the bits of the field"
+)]
+fn unpack_score(bits: u128) -> Score {
+    if bits >> 80 & 1 == 1 {
+        let color = if bits >> 32 & 1 == 1 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        Score::Win(color, bits as u32)
+    } else {
+        Score::Estimated(Estimated {
+            material: (bits as u16).cast_signed(),
+            king_safety: ((bits >> 16) as u16).cast_signed(),
+            square_control: ((bits >> 32) as u16).cast_signed(),
+            pawn_advancement: PawnAdvancement(
+                [0, 1, 2, 3].map(|i| CompoundI8::from_bits((bits >> (48 + i * 8)) as u8)),
+            ),
+        })
     }
-    pub fn set_max_capacity(&mut self, max_capacity: usize) {
-        self.max_capacity = Ord::min(max_capacity, <i32>::MAX as usize);
-        if self.capacity() > self.max_capacity {
-            self.clear_allocation();
+}
+impl TableValue {
+    fn pack(self) -> u128 {
+        let bound: u128 = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let depth = u8::try_from(self.depth).unwrap_or(u8::MAX);
+        pack_score(self.score)
+            | (bound << BOUND_SHIFT)
+            | (u128::from(depth) << DEPTH_SHIFT)
+            | (u128::from(self.best_move.map_or(0, Lan::to_bits)) << MOVE_SHIFT)
+            | (u128::from(self.age) << AGE_SHIFT)
+            | (1 << PRESENT_SHIFT)
+    }
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "casting only keeps the bits of the field"
+    )]
+    fn unpack(bits: u128) -> Option<Self> {
+        if bits >> PRESENT_SHIFT & 1 == 0 {
+            return None;
         }
+        let bound = match bits >> BOUND_SHIFT & 0b11 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        Some(TableValue {
+            score: unpack_score(bits),
+            depth: u32::from((bits >> DEPTH_SHIFT) as u8),
+            bound,
+            best_move: Lan::from_bits((bits >> MOVE_SHIFT) as u16),
+            age: (bits >> AGE_SHIFT) as u8,
+        })
+    }
+}
+// Written and read by every search thread at once without locking. The key is
+// stored XOR-ed with the data, so a slot torn by concurrent writes fails to
+// match any key instead of giving a mix of two entries.
+#[derive(Debug, Default)]
+struct Slot {
+    check: AtomicU64,
+    data: [AtomicU64; 2],
+}
+impl Slot {
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "the value is split into two words"
+    )]
+    fn store(&self, zobrist: u64, value: TableValue) {
+        let bits = value.pack();
+        let (low, high) = (bits as u64, (bits >> 64) as u64);
+        self.data[0].store(low, atomic::Ordering::Relaxed);
+        self.data[1].store(high, atomic::Ordering::Relaxed);
+        self.check
+            .store(zobrist ^ low ^ high, atomic::Ordering::Relaxed);
     }
-    fn get(&self, board: &HashableBoard) -> Option<&TableValue> {
-        self.table.get(board)
+    // The value along with the key it was stored with
+    fn load(&self) -> Option<(u64, TableValue)> {
+        let low = self.data[0].load(atomic::Ordering::Relaxed);
+        let high = self.data[1].load(atomic::Ordering::Relaxed);
+        let check = self.check.load(atomic::Ordering::Relaxed);
+        let value = TableValue::unpack(u128::from(low) | (u128::from(high) << 64))?;
+        Some((check ^ low ^ high, value))
     }
-    fn get_mut(&mut self, board: &HashableBoard) -> Option<&mut TableValue> {
-        self.table.get_mut(board)
+    fn clear(&mut self) {
+        *self.check.get_mut() = 0;
+        for word in &mut self.data {
+            *word.get_mut() = 0;
+        }
     }
-    fn inspect_element(&mut self, board: HashableBoard, f: impl FnOnce(&mut TableValue)) {
-        if let Some(value) = self.table.get_mut(&board) {
-            f(value);
-        } else {
-            let max_capacity = self.max_capacity.saturating_sub(self.capacity()) / 2;
-            if self.table.len() < self.capacity() || self.capacity() <= max_capacity {
-                let mut value = TableValue::default();
-                f(&mut value);
-                self.table.insert(board, value);
+}
+const BUCKET_SIZE: usize = 2;
+
+// The first slot keeps the deepest search and the second slot takes whatever
+// the first one doesn't
+type Bucket = [Slot; BUCKET_SIZE];
+
+// Allocated upfront to a fixed size, positions are placed in the bucket
+// indexed by their Zobrist key. Shared between threads.
+#[derive(Debug, Default)]
+pub struct Table {
+    buckets: Box<[Bucket]>,
+    // full boards of every slot, only present when verifying entries
+    boards: Option<Box<[Mutex<Option<HashableBoard>>]>>,
+    age: u8,
+}
+impl Table {
+    // `size` is in bytes, the table takes no more than that
+    pub fn new(size: usize) -> Self {
+        let mut table = Table::default();
+        table.set_size(size);
+        table
+    }
+    fn new_boards(len: usize) -> Box<[Mutex<Option<HashableBoard>>]> {
+        (0..len * BUCKET_SIZE).map(|_| Mutex::new(None)).collect()
+    }
+    // All entries are lost when the size changes
+    pub fn set_size(&mut self, size: usize) {
+        let len = size / size_of::<Bucket>();
+        if len != self.buckets.len() {
+            // the old allocation is freed first so both never coexist
+            self.buckets = Box::default();
+            self.buckets = (0..len).map(|_| Bucket::default()).collect();
+            if self.verification() {
+                self.boards = Some(Table::new_boards(len));
             }
         }
     }
-    fn insert_transposition(&mut self, board: HashableBoard, score: Score) {
-        self.inspect_element(board, |value| value.transposition = Some(score));
+    // In permille of the slots written by the current search, estimated from
+    // the first thousand slots
+    pub fn hash_full(&self) -> u32 {
+        let sample = &self.buckets[..Ord::min(1_000 / BUCKET_SIZE, self.buckets.len())];
+        let used = sample
+            .iter()
+            .flatten()
+            .filter(|slot| slot.load().is_some_and(|(_, value)| value.age == self.age))
+            .count();
+        (used * 1_000)
+            .checked_div(sample.len() * BUCKET_SIZE)
+            .map_or(0, |permille| u32::try_from(permille).unwrap())
     }
-    fn insert_repetition(&mut self, board: HashableBoard) {
-        self.inspect_element(board, |value| value.repetition = true);
+    fn verification(&self) -> bool {
+        self.boards.is_some()
     }
-    fn clear(&mut self) {
-        self.table.clear();
+    pub fn set_verification(&mut self, verification: bool) {
+        if verification != self.verification() {
+            self.clear();
+            self.boards = verification.then(|| Table::new_boards(self.buckets.len()));
+        }
+    }
+    fn is_verified(&self, slot: usize, key: &TableKey) -> bool {
+        match (&self.boards, &key.board) {
+            (Some(boards), Some(board)) => *boards[slot].lock().unwrap() == Some(*board),
+            _ => true,
+        }
     }
-    pub fn clear_allocation(&mut self) {
-        self.table = HashMap::default();
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "the remainder is less than the number of buckets"
+    )]
+    fn bucket_index(&self, zobrist: u64) -> Option<usize> {
+        if self.buckets.is_empty() {
+            None
+        } else {
+            Some((zobrist % self.buckets.len() as u64) as usize)
+        }
+    }
+    fn get(&self, key: &TableKey) -> Option<TableValue> {
+        let index = self.bucket_index(key.zobrist)?;
+        self.buckets[index]
+            .iter()
+            .enumerate()
+            .find_map(|(i, slot)| {
+                let (zobrist, value) = slot.load()?;
+                (zobrist == key.zobrist && self.is_verified(index * BUCKET_SIZE + i, key))
+                    .then_some(value)
+            })
+    }
+    // A slot already holding the position is always replaced so no stale
+    // copy is left behind. Otherwise the first slot is only replaced by a
+    // search at least as deep, unless it's left from an older search, and the
+    // second slot is always replaced.
+    fn insert(&self, key: TableKey, value: TableValue) {
+        let Some(index) = self.bucket_index(key.zobrist) else {
+            return;
+        };
+        let value = TableValue {
+            age: self.age,
+            ..value
+        };
+        let bucket = &self.buckets[index];
+        let old = bucket.each_ref().map(Slot::load);
+        let matching = old
+            .iter()
+            .position(|old| old.is_some_and(|(zobrist, _)| zobrist == key.zobrist));
+        let i = matching.unwrap_or(match old[0] {
+            Some((_, old)) if old.age == value.age && old.depth > value.depth => 1,
+            _ => 0,
+        });
+        if let (Some(boards), Some(board)) = (&self.boards, key.board) {
+            *boards[index * BUCKET_SIZE + i].lock().unwrap() = Some(board);
+        }
+        bucket[i].store(key.zobrist, value);
+    }
+    // Marks the start of a new search, entries of previous ones are kept but
+    // get replaced first
+    pub fn age(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+    pub fn clear(&mut self) {
+        for slot in self.buckets.iter_mut().flatten() {
+            slot.clear();
+        }
+        if let Some(boards) = &mut self.boards {
+            for board in boards {
+                *board.get_mut().unwrap() = None;
+            }
+        }
     }
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -468,6 +1398,71 @@ impl AlphaBetaState {
             },
         }
     }
+    // With multiple principal variations, children are searched with a window
+    // only narrowed by the worst of the best scores so far, so the scores of
+    // the best `multi_pv` children are exact
+    fn multi_pv_window(self, setting: SearchSetting, searched: &[MoveTreePair]) -> Self {
+        if setting.ply != 0 || setting.multi_pv <= 1 {
+            return self;
+        }
+        let mut window = AlphaBetaState::new(self.current_player, setting);
+        let mut scores: Vec<_> = searched
+            .iter()
+            .filter_map(|(_, _, game_tree)| game_tree.score)
+            .map(|score| score.to_root(1))
+            .collect();
+        if scores.len() >= setting.multi_pv {
+            scores.sort_unstable();
+            let worst = match self.current_player {
+                Color::White => scores[scores.len() - setting.multi_pv],
+                Color::Black => scores[setting.multi_pv - 1],
+            };
+            window.set(worst);
+        }
+        window
+    }
+    // The narrowest window just short of the bound the current player needs
+    // to reach for a cutoff, paired with that bound. A score improving within
+    // it reaches the bound.
+    fn null_window(self) -> Option<(Self, Score)> {
+        match self.current_player {
+            Color::White => {
+                let bound = self.beta.into_finite()?;
+                Some((self.with_window(prev(bound), self.beta), bound))
+            }
+            Color::Black => {
+                let bound = self.alpha.into_finite()?;
+                Some((self.with_window(self.alpha, next(bound)), bound))
+            }
+        }
+    }
+    // The narrowest window just past the score the current player already
+    // has. A score improving within it needs an exact search.
+    fn pvs_window(self) -> Option<Self> {
+        match self.current_player {
+            Color::White => Some(self.with_window(self.alpha, next(self.alpha.into_finite()?))),
+            Color::Black => Some(self.with_window(prev(self.beta.into_finite()?), self.beta)),
+        }
+    }
+    fn with_window(self, alpha: Extended<Score>, beta: Extended<Score>) -> Self {
+        AlphaBetaState {
+            alpha,
+            beta,
+            ..self
+        }
+    }
+    fn is_within(self, score: Score) -> bool {
+        let score = Extended::Finite(score);
+        self.alpha < score && score < self.beta
+    }
+    // Whether the score would raise alpha for white or lower beta for black
+    fn improves(self, score: Score) -> bool {
+        let score = Extended::Finite(score);
+        match self.current_player {
+            Color::White => score > self.alpha,
+            Color::Black => score < self.beta,
+        }
+    }
     fn set(&mut self, score: Score) -> bool {
         let score = Extended::Finite(score);
         match self.current_player {
@@ -489,3 +1484,339 @@ impl AlphaBetaState {
         false
     }
 }
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicI64, Ordering},
+    };
+
+    use crate::{
+        board::{Board, Lan},
+        color::Color,
+        end_state::{EndReason, EndState},
+        fen::Fen,
+        game_tree::{
+            Bound, Bucket, GameTree, GameTreeInner, SearchOptions, SearchSetting, Shared, Table,
+            TableKey, TableValue,
+        },
+        heuristics::{Estimated, PawnAdvancement, Score},
+        history::History,
+        misc::{Extended, MEBIBYTES},
+        move_order::MoveOrder,
+    };
+
+    fn from_fen(fen: &str) -> Board {
+        let fen: Fen = fen.parse().unwrap();
+        fen.try_into().unwrap()
+    }
+
+    #[test]
+    fn recapture_past_the_horizon() {
+        let mut game_tree = GameTree::new(from_fen("k7/8/2p5/3p4/8/8/8/3QK3 w - - 0 1"));
+        game_tree.calculate(1, &mut Table::new(MEBIBYTES), 1);
+        assert_ne!(game_tree.best_move(), Some("d1d5".parse::<Lan>().unwrap()));
+    }
+    #[test]
+    fn faster_mate() {
+        let mut game_tree = GameTree::new(from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1"));
+        game_tree.calculate(4, &mut Table::new(MEBIBYTES), 1);
+        assert_eq!(game_tree.best_move(), Some("h1h8".parse().unwrap()));
+        assert_eq!(game_tree.score(), Some(Score::Win(Color::White, 1)));
+    }
+    #[test]
+    fn multi_pv_scores_are_exact() {
+        let board = from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let mut game_tree = GameTree::new(board.clone());
+        game_tree.calculate_raw(3, &mut Table::new(MEBIBYTES), 1, 3, None, None, None);
+        for (score, bound, mut line) in game_tree.lines().take(3) {
+            assert_eq!(bound, Bound::Exact);
+            let mut child = GameTree::new(board.clone());
+            child.move_piece(line.next().unwrap());
+            child.calculate(2, &mut Table::new(0), 1);
+            assert_eq!(Some(score), child.score().map(|score| score.to_root(1)));
+        }
+    }
+    #[test]
+    fn only_best_lines_are_exact() {
+        let mut game_tree = GameTree::new(from_fen(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        ));
+        game_tree.calculate(3, &mut Table::new(MEBIBYTES), 1);
+        let lines: Vec<_> = game_tree
+            .lines()
+            .map(|(_, bound, mut line)| (bound, line.next().unwrap()))
+            .collect();
+        assert_eq!(lines[0].0, Bound::Exact);
+        assert!(lines[1..].iter().all(|(bound, _)| *bound == Bound::Upper));
+        game_tree.move_piece(lines[1].1);
+        assert_eq!(game_tree.bound(), Bound::Upper);
+    }
+    #[test]
+    fn stopped_aspiration_window_leaves_a_bound() {
+        // the mate is only found at depth 3, failing high on the window
+        // around the estimate of depth 2
+        let mate = Some(Score::Win(Color::White, 3));
+        let search = |budget: Option<i64>| {
+            let mut game_tree = GameTree::new(from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1"));
+            let mut table = Table::new(MEBIBYTES);
+            game_tree.calculate(2, &mut table, 1);
+            let budget = budget.map(|budget| Arc::new(AtomicI64::new(budget)));
+            game_tree.calculate_raw(4, &mut table, 1, 1, None, None, budget.as_ref());
+            (game_tree.bound(), game_tree.score())
+        };
+        assert_eq!(search(None), (Bound::Exact, mate));
+        assert!(
+            (1000..2000)
+                .step_by(25)
+                .any(|budget| search(Some(budget)) == (Bound::Lower, mate))
+        );
+    }
+    #[test]
+    fn search_moves() {
+        let mut game_tree = GameTree::new(from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1"));
+        let search_moves = ["h1h2".parse().unwrap(), "h1g1".parse().unwrap()];
+        game_tree.calculate_raw(
+            2,
+            &mut Table::new(MEBIBYTES),
+            1,
+            3,
+            Some(&search_moves),
+            None,
+            None,
+        );
+        assert!(search_moves.contains(&game_tree.best_move().unwrap()));
+        assert_eq!(game_tree.lines().count(), 2);
+        game_tree.calculate(2, &mut Table::new(MEBIBYTES), 1);
+        assert_eq!(game_tree.best_move(), Some("h1h8".parse().unwrap()));
+    }
+    #[test]
+    fn illegal_search_moves_are_ignored() {
+        let mut game_tree = GameTree::new(from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1"));
+        let search_moves = ["a1a2".parse().unwrap()];
+        game_tree.calculate_raw(
+            2,
+            &mut Table::new(MEBIBYTES),
+            1,
+            1,
+            Some(&search_moves),
+            None,
+            None,
+        );
+        assert_eq!(game_tree.best_move(), Some("h1h8".parse().unwrap()));
+        assert!(game_tree.score().is_some());
+    }
+    #[test]
+    fn null_move_pruning_keeps_mate() {
+        for null_move_pruning in [false, true] {
+            let mut game_tree = GameTree::new(from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1"));
+            game_tree.set_options(SearchOptions {
+                null_move_pruning,
+                ..SearchOptions::default()
+            });
+            game_tree.calculate(4, &mut Table::new(MEBIBYTES), 1);
+            assert_eq!(game_tree.best_move(), Some("h1h8".parse().unwrap()));
+            assert_eq!(game_tree.score(), Some(Score::Win(Color::White, 1)));
+        }
+    }
+    #[test]
+    fn principal_variation_search_is_exact() {
+        for fen in [
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            let mut game_tree = GameTree::new(from_fen(fen));
+            game_tree.set_options(SearchOptions::UNPRUNED);
+            let mut table = Table::new(MEBIBYTES);
+            let mut pvs_game_tree = GameTree::new(from_fen(fen));
+            let mut pvs_table = Table::new(MEBIBYTES);
+            pvs_game_tree.set_options(SearchOptions {
+                principal_variation_search: true,
+                aspiration_windows: true,
+                ..SearchOptions::UNPRUNED
+            });
+            for depth in 1..=3 {
+                game_tree.calculate(depth, &mut table, 1);
+                pvs_game_tree.calculate(depth, &mut pvs_table, 1);
+                assert_eq!(
+                    game_tree.score(),
+                    pvs_game_tree.score(),
+                    "{fen} at depth {depth}"
+                );
+            }
+        }
+    }
+    #[test]
+    fn bounded_memory_keeps_scores_and_lines() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let mut game_tree = GameTree::new(from_fen(fen));
+        game_tree.set_options(SearchOptions::UNPRUNED);
+        let mut bounded_game_tree = GameTree::new(from_fen(fen));
+        bounded_game_tree.set_options(SearchOptions {
+            bounded_memory: true,
+            ..SearchOptions::UNPRUNED
+        });
+        for depth in 1..=4 {
+            game_tree.calculate(depth, &mut Table::new(0), 1);
+            bounded_game_tree.calculate(depth, &mut Table::new(0), 1);
+            assert_eq!(game_tree.score(), bounded_game_tree.score());
+            assert_eq!(bounded_game_tree.best_line().count(), depth as usize);
+        }
+        let movement = bounded_game_tree.best_move().unwrap();
+        bounded_game_tree.move_piece(movement);
+        bounded_game_tree.calculate(3, &mut Table::new(0), 1);
+        assert!(bounded_game_tree.best_move().is_some());
+    }
+    #[test]
+    fn bounded_memory_keeps_fifty_move_rule() {
+        let mut game_tree = GameTree::new(from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 97 80"));
+        game_tree.set_options(SearchOptions {
+            bounded_memory: true,
+            ..SearchOptions::default()
+        });
+        let draw = Score::from_end_state(EndState::Draw(EndReason::MoveRule));
+        for depth in 1..=5 {
+            game_tree.calculate(depth, &mut Table::new(0), 1);
+            if depth >= 3 {
+                assert_eq!(game_tree.score(), Some(draw), "depth {depth}");
+            }
+        }
+    }
+    #[test]
+    fn table_is_kept_between_searches() {
+        let board = from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let mut table = Table::new(MEBIBYTES);
+        let mut game_tree = GameTree::new(board.clone());
+        let nodes = game_tree.calculate(4, &mut table, 1);
+        let mut new_game_tree = GameTree::new(board);
+        let new_nodes = new_game_tree.calculate(4, &mut table, 1);
+        assert!(new_nodes * 4 < nodes, "{new_nodes} out of {nodes}");
+        assert_eq!(game_tree.score(), new_game_tree.score());
+        assert_eq!(game_tree.best_move(), new_game_tree.best_move());
+    }
+    #[test]
+    fn table_replacement() {
+        let mut table = Table::new(size_of::<Bucket>());
+        let key = |zobrist| TableKey {
+            zobrist,
+            board: None,
+        };
+        let value = |depth| TableValue {
+            score: Score::Estimated(Estimated::default()),
+            depth,
+            bound: Bound::Exact,
+            best_move: None,
+            age: 0,
+        };
+        table.insert(key(1), value(5));
+        table.insert(key(2), value(3));
+        table.insert(key(3), value(4));
+        assert_eq!(table.hash_full(), 1_000);
+        assert_eq!(table.get(&key(1)).map(|value| value.depth), Some(5));
+        assert_eq!(table.get(&key(2)), None);
+        assert_eq!(table.get(&key(3)).map(|value| value.depth), Some(4));
+        table.age();
+        table.insert(key(4), value(1));
+        assert_eq!(table.get(&key(1)), None);
+        assert_eq!(table.get(&key(4)).map(|value| value.depth), Some(1));
+        assert_eq!(table.hash_full(), 500);
+        table.clear();
+        assert_eq!(table.hash_full(), 0);
+
+        // a shallower search of the same position replaces its own slot
+        table.insert(key(1), value(5));
+        table.insert(key(1), value(3));
+        assert_eq!(table.get(&key(1)).map(|value| value.depth), Some(3));
+        assert_eq!(table.hash_full(), 500);
+    }
+    #[test]
+    fn table_value_round_trip() {
+        let estimated = Estimated {
+            material: -3,
+            king_safety: 12,
+            square_control: -40,
+            pawn_advancement: PawnAdvancement::MIN,
+        };
+        for (score, best_move) in [
+            (Score::Estimated(estimated), Some("e7e8q".parse().unwrap())),
+            (
+                Score::Estimated(Estimated::MAX),
+                Some("e1h1".parse().unwrap()),
+            ),
+            (Score::Win(Color::Black, 7), None),
+            (Score::Win(Color::White, u32::MAX), None),
+        ] {
+            let value = TableValue {
+                score,
+                depth: 9,
+                bound: Bound::Upper,
+                best_move,
+                age: 200,
+            };
+            assert_eq!(TableValue::unpack(value.pack()), Some(value));
+        }
+        assert_eq!(TableValue::unpack(0), None);
+    }
+    #[test]
+    fn node_budget() {
+        let mut game_tree = GameTree::new(Board::starting_position());
+        let mut table = Table::new(MEBIBYTES);
+        let budget = Arc::new(AtomicI64::new(5000));
+        let mut nodes = 0;
+        for depth in 1.. {
+            nodes += game_tree.calculate_raw(depth, &mut table, 1, 1, None, None, Some(&budget));
+            if budget.load(Ordering::Relaxed) <= 0 {
+                break;
+            }
+        }
+        assert_eq!(nodes, 5000);
+        assert!(game_tree.best_move().is_some());
+    }
+    #[test]
+    fn null_move_board_keeps_move_counters() {
+        let board = from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 98 80");
+        let mut game_tree = GameTreeInner::new(board.clone());
+        game_tree.children_or_init();
+        assert_eq!(game_tree.to_board(), Some(board));
+    }
+    #[test]
+    fn null_move_skips_mate_bounds() {
+        let board = from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1");
+        let shared = Shared {
+            table: Arc::new(Table::new(MEBIBYTES)),
+            history: Arc::new(History::new()),
+            move_order: Arc::new(MoveOrder::new()),
+            multi_pv: 1,
+            search_moves: None,
+            stop_signal: Arc::new(AtomicBool::new(false)),
+            node_budget: None,
+            options: SearchOptions::default(),
+        };
+        let mut game_tree = GameTreeInner::new(board.clone());
+        let pawn = Score::Estimated(Estimated {
+            material: 1,
+            ..Estimated::default()
+        });
+        for (beta, cutoff) in [(Score::Win(Color::White, 20), false), (pawn, true)] {
+            let setting = SearchSetting {
+                ply: 1,
+                beta: Extended::Finite(beta),
+                ..shared.setting(6)
+            };
+            let (score, _) = game_tree.null_move(setting);
+            assert_eq!(score.is_some(), cutoff, "{beta:?}");
+            assert_eq!(game_tree.to_board(), Some(board.clone()));
+        }
+    }
+    #[test]
+    fn helpers_keep_fifty_move_rule() {
+        let mut game_tree = GameTree::new(from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 96 80"));
+        let mut table = Table::new(MEBIBYTES);
+        game_tree.calculate(1, &mut table, 1);
+        let draw = Score::from_end_state(EndState::Draw(EndReason::MoveRule));
+        for depth in 4..=6 {
+            game_tree.calculate(depth, &mut table, 4);
+            assert_eq!(game_tree.score(), Some(draw), "depth {depth}");
+        }
+    }
+}
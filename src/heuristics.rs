@@ -9,6 +9,9 @@ use crate::{color::Color, end_state::EndState, misc::CompoundI8};
 pub struct PawnAdvancement(pub [CompoundI8; 4]);
 
 impl PawnAdvancement {
+    pub const MIN: Self = PawnAdvancement([CompoundI8::MIN; 4]);
+    pub const MAX: Self = PawnAdvancement([CompoundI8::MAX; 4]);
+
     pub fn new(array: [i8; 8]) -> Self {
         PawnAdvancement([
             CompoundI8::new(array[0], array[1]),
@@ -17,6 +20,30 @@ impl PawnAdvancement {
             CompoundI8::new(array[6], array[7]),
         ])
     }
+    // The adjacent values according to `Ord`, the last element is the least
+    // significant
+    pub fn next(self) -> Option<Self> {
+        let mut pawn_advancement = self;
+        for value in pawn_advancement.0.iter_mut().rev() {
+            if let Some(next) = value.next() {
+                *value = next;
+                return Some(pawn_advancement);
+            }
+            *value = CompoundI8::MIN;
+        }
+        None
+    }
+    pub fn prev(self) -> Option<Self> {
+        let mut pawn_advancement = self;
+        for value in pawn_advancement.0.iter_mut().rev() {
+            if let Some(prev) = value.prev() {
+                *value = prev;
+                return Some(pawn_advancement);
+            }
+            *value = CompoundI8::MAX;
+        }
+        None
+    }
 }
 impl Neg for PawnAdvancement {
     type Output = Self;
@@ -61,12 +88,90 @@ impl SubAssign for PawnAdvancement {
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub struct Estimated {
-    pub material: i8,
-    pub king_safety: i8,
+    pub material: i16,
+    pub king_safety: i16,
     pub square_control: i16,
     pub pawn_advancement: PawnAdvancement,
 }
 impl Estimated {
+    pub const MIN: Self = Estimated {
+        material: i16::MIN,
+        king_safety: i16::MIN,
+        square_control: i16::MIN,
+        pawn_advancement: PawnAdvancement::MIN,
+    };
+    pub const MAX: Self = Estimated {
+        material: i16::MAX,
+        king_safety: i16::MAX,
+        square_control: i16::MAX,
+        pawn_advancement: PawnAdvancement::MAX,
+    };
+
+    // The adjacent values according to `Ord`, fields are compared in order of
+    // declaration so the last is the least significant. These allow windows
+    // with no score in between their bounds.
+    pub fn next(self) -> Option<Self> {
+        if let Some(pawn_advancement) = self.pawn_advancement.next() {
+            return Some(Estimated {
+                pawn_advancement,
+                ..self
+            });
+        }
+        let pawn_advancement = PawnAdvancement::MIN;
+        if let Some(square_control) = self.square_control.checked_add(1) {
+            return Some(Estimated {
+                square_control,
+                pawn_advancement,
+                ..self
+            });
+        }
+        let square_control = i16::MIN;
+        if let Some(king_safety) = self.king_safety.checked_add(1) {
+            return Some(Estimated {
+                king_safety,
+                square_control,
+                pawn_advancement,
+                ..self
+            });
+        }
+        Some(Estimated {
+            material: self.material.checked_add(1)?,
+            king_safety: i16::MIN,
+            square_control,
+            pawn_advancement,
+        })
+    }
+    pub fn prev(self) -> Option<Self> {
+        if let Some(pawn_advancement) = self.pawn_advancement.prev() {
+            return Some(Estimated {
+                pawn_advancement,
+                ..self
+            });
+        }
+        let pawn_advancement = PawnAdvancement::MAX;
+        if let Some(square_control) = self.square_control.checked_sub(1) {
+            return Some(Estimated {
+                square_control,
+                pawn_advancement,
+                ..self
+            });
+        }
+        let square_control = i16::MAX;
+        if let Some(king_safety) = self.king_safety.checked_sub(1) {
+            return Some(Estimated {
+                king_safety,
+                square_control,
+                pawn_advancement,
+                ..self
+            });
+        }
+        Some(Estimated {
+            material: self.material.checked_sub(1)?,
+            king_safety: i16::MAX,
+            square_control,
+            pawn_advancement,
+        })
+    }
     pub fn centipawn(self) -> i32 {
         <i32>::from(self.material) * 100
             + <i32>::from(self.king_safety) * 10
@@ -116,25 +221,63 @@ impl SubAssign for Estimated {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 
 pub enum Score {
-    Win(Color),
+    // Carries the number of plies until checkmate
+    Win(Color, u32),
     Estimated(Estimated),
 }
 impl Score {
-    pub const WHITE_WINS: Self = Score::Win(Color::White);
-    pub const BLACK_WINS: Self = Score::Win(Color::Black);
-
     pub fn from_end_state(end_state: EndState) -> Self {
         match end_state {
-            EndState::Win(color) => Score::Win(color),
-            EndState::Draw => Score::Estimated(Estimated::default()),
+            EndState::Win(color, _) => Score::Win(color, 0),
+            EndState::Draw(_) => Score::Estimated(Estimated::default()),
         }
     }
-    pub fn is_win(self) -> bool {
-        matches!(self, Score::Win(_))
+    // The plies of a win stored in a position is counted from that position,
+    // while during search it's counted from the root. These convert between them.
+    pub fn to_position(self, ply: u32) -> Self {
+        match self {
+            Score::Win(color, plies) => Score::Win(color, plies.saturating_sub(ply)),
+            score @ Score::Estimated(_) => score,
+        }
+    }
+    pub fn to_root(self, ply: u32) -> Self {
+        match self {
+            Score::Win(color, plies) => Score::Win(color, plies + ply),
+            score @ Score::Estimated(_) => score,
+        }
+    }
+    // The adjacent scores according to `Ord`
+    pub fn next(self) -> Option<Self> {
+        match self {
+            Score::Estimated(estimated) => Some(
+                estimated
+                    .next()
+                    .map_or(Score::Win(Color::White, u32::MAX), Score::Estimated),
+            ),
+            Score::Win(Color::White, plies) => {
+                Some(Score::Win(Color::White, plies.checked_sub(1)?))
+            }
+            Score::Win(Color::Black, u32::MAX) => Some(Score::Estimated(Estimated::MIN)),
+            Score::Win(Color::Black, plies) => Some(Score::Win(Color::Black, plies + 1)),
+        }
+    }
+    pub fn prev(self) -> Option<Self> {
+        match self {
+            Score::Estimated(estimated) => Some(
+                estimated
+                    .prev()
+                    .map_or(Score::Win(Color::Black, u32::MAX), Score::Estimated),
+            ),
+            Score::Win(Color::Black, plies) => {
+                Some(Score::Win(Color::Black, plies.checked_sub(1)?))
+            }
+            Score::Win(Color::White, u32::MAX) => Some(Score::Estimated(Estimated::MAX)),
+            Score::Win(Color::White, plies) => Some(Score::Win(Color::White, plies + 1)),
+        }
     }
     pub fn centipawn(self) -> Centipawn {
         match self {
-            Score::Win(color) => Centipawn::Win(color),
+            Score::Win(color, plies) => Centipawn::Win(color, plies),
             Score::Estimated(estimated) => Centipawn::Centipawn(estimated.centipawn()),
         }
     }
@@ -154,23 +297,72 @@ impl Ord for Score {
         match (self, other) {
             (Score::Estimated(a), Score::Estimated(b)) => Ord::cmp(a, b),
 
-            (&Score::BLACK_WINS, &Score::BLACK_WINS) | (&Score::WHITE_WINS, &Score::WHITE_WINS) => {
-                Ordering::Equal
-            }
+            // faster wins and slower losses are preferred
+            (Score::Win(Color::White, a), Score::Win(Color::White, b)) => Ord::cmp(b, a),
+            (Score::Win(Color::Black, a), Score::Win(Color::Black, b)) => Ord::cmp(a, b),
 
-            (_, &Score::BLACK_WINS) | (&Score::WHITE_WINS, _) => Ordering::Greater,
+            (_, Score::Win(Color::Black, _)) | (Score::Win(Color::White, _), _) => {
+                Ordering::Greater
+            }
 
-            (&Score::BLACK_WINS, _) | (_, &Score::WHITE_WINS) => Ordering::Less,
+            (Score::Win(Color::Black, _), _) | (_, Score::Win(Color::White, _)) => Ordering::Less,
         }
     }
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Centipawn {
     Centipawn(i32),
-    Win(Color),
+    Win(Color, u32),
 }
 impl Default for Centipawn {
     fn default() -> Self {
         Centipawn::Centipawn(0)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        color::Color,
+        heuristics::{Estimated, PawnAdvancement, Score},
+    };
+
+    #[test]
+    fn next_and_prev() {
+        let estimated = Estimated {
+            material: 2,
+            king_safety: -1,
+            square_control: i16::MAX,
+            pawn_advancement: PawnAdvancement::MAX,
+        };
+        let scores = [
+            Score::Win(Color::Black, 0),
+            Score::Win(Color::Black, u32::MAX),
+            Score::Estimated(Estimated::MIN),
+            Score::Estimated(estimated),
+            Score::Estimated(Estimated::MAX),
+            Score::Win(Color::White, u32::MAX),
+            Score::Win(Color::White, 0),
+        ];
+        assert_eq!(scores[0].prev(), None);
+        assert_eq!(scores[6].next(), None);
+        for pair in scores.windows(2) {
+            assert!(pair[0] < pair[1]);
+        }
+        for score in scores {
+            if let Some(next) = score.next() {
+                assert!(next > score);
+                assert_eq!(next.prev(), Some(score));
+            }
+        }
+        assert_eq!(scores[1].next(), Some(scores[2]));
+        assert_eq!(scores[4].next(), Some(scores[5]));
+        let Some(Score::Estimated(next)) = scores[3].next() else {
+            panic!()
+        };
+        assert_eq!(next.material, 2);
+        assert_eq!(next.king_safety, 0);
+        assert_eq!(next.square_control, i16::MIN);
+        assert_eq!(next.pawn_advancement, PawnAdvancement::MIN);
+    }
+}
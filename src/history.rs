@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+
+use rustc_hash::FxHashMap;
+
+// The fivefold repetition is the automatic draw, but threefold repetition can
+// always be claimed, so positions reaching it are treated as drawn
+pub const REPETITION_DRAW: u8 = 3;
+
+#[derive(Debug, Clone, Default)]
+// Positions are identified by their Zobrist key
+pub struct History(FxHashMap<u64, u8>);
+
+impl History {
+    pub fn new() -> Self {
+        History(HashMap::default())
+    }
+    pub fn push(&mut self, zobrist: u64) {
+        *self.0.entry(zobrist).or_default() += 1;
+    }
+    pub fn count(&self, zobrist: u64) -> u8 {
+        self.0.get(&zobrist).copied().unwrap_or(0)
+    }
+    pub fn contains(&self, zobrist: u64) -> bool {
+        self.0.contains_key(&zobrist)
+    }
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
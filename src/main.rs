@@ -9,8 +9,10 @@ use std::{
     str::FromStr,
 };
 
-use crate::{fuzz::fuzz, repl::repl, uci::uci_loop};
+use crate::{bench::bench_loop, fuzz::fuzz, perft::perft_loop, repl::repl, uci::uci_loop};
 
+mod bench;
+mod bitboard;
 mod board;
 mod board_display;
 mod castling_right;
@@ -22,17 +24,27 @@ mod fen;
 mod fuzz;
 mod game_tree;
 mod heuristics;
+mod history;
 mod misc;
+mod move_order;
+mod perft;
+mod pgn;
 mod piece;
 mod repl;
+mod san;
 mod simple_board;
+mod thread_pool;
+mod time_manager;
 mod uci;
+mod zobrist;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Input {
     Uci,
     Repl,
     Fuzz,
+    Perft,
+    Bench,
 }
 impl Display for Input {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -40,6 +52,8 @@ impl Display for Input {
             Input::Uci => write!(f, "uci")?,
             Input::Repl => write!(f, "repl")?,
             Input::Fuzz => write!(f, "fuzz")?,
+            Input::Perft => write!(f, "perft")?,
+            Input::Bench => write!(f, "bench")?,
         }
         Ok(())
     }
@@ -52,6 +66,8 @@ impl FromStr for Input {
             "uci" => Ok(Input::Uci),
             "repl" => Ok(Input::Repl),
             "fuzz" => Ok(Input::Fuzz),
+            "perft" => Ok(Input::Perft),
+            "bench" => Ok(Input::Bench),
             _ => Err(ParseInputError),
         }
     }
@@ -61,7 +77,10 @@ struct ParseInputError;
 
 impl Display for ParseInputError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "provided string was not `uci`, `repl`, or `fuzz`")?;
+        write!(
+            f,
+            "provided string was not `uci`, `repl`, `fuzz`, `perft`, or `bench`"
+        )?;
         Ok(())
     }
 }
@@ -82,6 +101,8 @@ fn main() {
         Input::Uci => uci_loop(),
         Input::Repl => repl(),
         Input::Fuzz => fuzz(),
+        Input::Perft => perft_loop(),
+        Input::Bench => bench_loop(),
     }
 }
 #[macro_export]
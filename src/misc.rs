@@ -43,7 +43,10 @@ pub fn extract_prefix_token(src: &str) -> &str {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct CompoundI8(i8);
 impl CompoundI8 {
-    pub fn new(left: i8, right: i8) -> Self {
+    pub const MIN: Self = CompoundI8::new(-8, -8);
+    pub const MAX: Self = CompoundI8::new(7, 7);
+
+    pub const fn new(left: i8, right: i8) -> Self {
         debug_assert!(left < 8);
         debug_assert!(left >= -8);
         debug_assert!(right < 8);
@@ -56,6 +59,31 @@ impl CompoundI8 {
     pub fn right(self) -> i8 {
         (self.0 << 4) >> 4
     }
+    pub fn to_bits(self) -> u8 {
+        self.0.cast_unsigned()
+    }
+    pub fn from_bits(bits: u8) -> Self {
+        CompoundI8(bits.cast_signed())
+    }
+    // The adjacent values according to `Ord`
+    pub fn next(self) -> Option<Self> {
+        if self.right() < 7 {
+            Some(CompoundI8::new(self.left(), self.right() + 1))
+        } else if self.left() < 7 {
+            Some(CompoundI8::new(self.left() + 1, -8))
+        } else {
+            None
+        }
+    }
+    pub fn prev(self) -> Option<Self> {
+        if self.right() > -8 {
+            Some(CompoundI8::new(self.left(), self.right() - 1))
+        } else if self.left() > -8 {
+            Some(CompoundI8::new(self.left() - 1, 7))
+        } else {
+            None
+        }
+    }
 }
 impl Default for CompoundI8 {
     fn default() -> Self {
@@ -105,6 +133,7 @@ impl SubAssign<CompoundI8> for CompoundI8 {
 }
 // TODO: replace with `std::hint::cold_path` when stable
 #[cold]
+#[allow(dead_code, reason = "no path is cold at the moment")]
 pub fn cold_path() {}
 
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -165,4 +194,14 @@ mod test {
         assert_eq!(compound.left(), -8);
         assert_eq!(compound.right(), 7);
     }
+    #[test]
+    fn next_and_prev() {
+        let mut compound = CompoundI8::MIN;
+        while let Some(next) = compound.next() {
+            assert!(next > compound);
+            assert_eq!(next.prev(), Some(compound));
+            compound = next;
+        }
+        assert_eq!(compound, CompoundI8::MAX);
+    }
 }
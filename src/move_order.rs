@@ -0,0 +1,152 @@
+use std::{
+    cmp::Reverse,
+    sync::atomic::{AtomicU16, AtomicU32, Ordering},
+};
+
+use crate::{
+    board::{HashableBoard, Lan},
+    piece::PieceKind,
+};
+
+const MAX_PLY: usize = 128;
+const KILLERS_PER_PLY: usize = 2;
+
+// Quiet moves neither capture nor promote, castling is encoded as the king
+// capturing its own rook and is quiet as well
+pub fn is_quiet(board: &HashableBoard, movement: Lan) -> bool {
+    movement.promotion.is_none()
+        && board.board[movement.destination]
+            .is_none_or(|piece| piece.color() == board.current_player)
+        && Some(movement.destination) != board.en_passant_target
+}
+fn value(piece: PieceKind) -> u32 {
+    piece.value().map_or(10, |value| value.get().into())
+}
+fn mvv_lva(board: &HashableBoard, movement: Lan) -> u32 {
+    let victim = match board.board[movement.destination] {
+        Some(piece) if piece.color() != board.current_player => value(piece.piece()),
+        None if Some(movement.destination) == board.en_passant_target => 1,
+        _ => 0,
+    };
+    let promotion = movement.promotion.map_or(0, value);
+    let attacker = board.board[movement.origin].map_or(0, |piece| value(piece.piece()));
+    (victim + promotion) * 16 + 16 - attacker
+}
+// Orders the moves of nodes searched for the first time, which have no
+// previous scores to sort by. Shared between threads.
+#[derive(Debug)]
+pub struct MoveOrder {
+    // quiet moves that caused a cutoff, indexed by ply, most recent first, 0
+    // when empty
+    killers: Box<[[AtomicU16; KILLERS_PER_PLY]]>,
+    // how good a quiet move has been for cutoffs, indexed by color, origin,
+    // then destination
+    history: Box<[AtomicU32]>,
+}
+impl MoveOrder {
+    pub fn new() -> Self {
+        MoveOrder {
+            killers: (0..MAX_PLY)
+                .map(|_| [AtomicU16::new(0), AtomicU16::new(0)])
+                .collect(),
+            history: (0..2 * 64 * 64).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+    fn history_index(board: &HashableBoard, movement: Lan) -> usize {
+        ((board.current_player as usize) << 12)
+            | (usize::from(movement.origin.index()) << 6)
+            | usize::from(movement.destination.index())
+    }
+    fn killer_priority(&self, movement: Lan, ply: u32) -> Option<u32> {
+        let killers = self.killers.get(ply as usize)?;
+        killers
+            .iter()
+            .rev()
+            .zip(1..)
+            .find(|(killer, _)| killer.load(Ordering::Relaxed) == movement.to_bits())
+            .map(|(_, priority)| priority)
+    }
+    // Captures and promotions come first by most valuable victim then least
+    // valuable attacker, then killer moves, then the rest by history
+    pub fn sort_key(&self, board: &HashableBoard, movement: Lan, ply: u32) -> impl Ord {
+        let key = if !is_quiet(board, movement) {
+            (2, mvv_lva(board, movement))
+        } else if let Some(priority) = self.killer_priority(movement, ply) {
+            (1, priority)
+        } else {
+            let history = &self.history[Self::history_index(board, movement)];
+            (0, history.load(Ordering::Relaxed))
+        };
+        Reverse(key)
+    }
+    pub fn record_cutoff(&self, board: &HashableBoard, movement: Lan, ply: u32, depth: u32) {
+        if !is_quiet(board, movement) {
+            return;
+        }
+        if let Some(killers) = self.killers.get(ply as usize) {
+            let movement = movement.to_bits();
+            if killers[0].load(Ordering::Relaxed) != movement {
+                let previous = killers[0].swap(movement, Ordering::Relaxed);
+                killers[1].store(previous, Ordering::Relaxed);
+            }
+        }
+        let _ = self.history[Self::history_index(board, movement)].fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |value| Some(value.saturating_add(depth * depth)),
+        );
+    }
+    // Killers are forgotten since plies shift once a move is made, while
+    // history is kept but weighs less over time
+    pub fn age(&self) {
+        for killers in &self.killers {
+            for killer in killers {
+                killer.store(0, Ordering::Relaxed);
+            }
+        }
+        for value in &self.history {
+            value.store(value.load(Ordering::Relaxed) / 2, Ordering::Relaxed);
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{
+        board::{Board, Lan},
+        fen::Fen,
+        move_order::MoveOrder,
+    };
+
+    #[test]
+    fn order() {
+        let board: Fen = "4k3/8/8/3q1r2/4P3/8/8/1R3K2 w B - 0 1".parse().unwrap();
+        let board: Board = board.try_into().unwrap();
+        let board = board.as_hashable();
+        let move_order = MoveOrder::new();
+        let killer: Lan = "b1b7".parse().unwrap();
+        let castling: Lan = "f1b1".parse().unwrap();
+        move_order.record_cutoff(&board, killer, 1, 3);
+        let mut moves: Vec<Lan> = ["b1b2", "b1b7", "e4f5", "e4d5", "f1g1", "f1b1"]
+            .into_iter()
+            .map(|movement| movement.parse().unwrap())
+            .collect();
+        moves.sort_by_cached_key(|movement| move_order.sort_key(&board, *movement, 1));
+        assert_eq!(
+            moves[..3],
+            ["e4d5".parse().unwrap(), "e4f5".parse().unwrap(), killer,]
+        );
+
+        // castling is quiet so it can be a killer move
+        move_order.record_cutoff(&board, castling, 1, 3);
+        moves.sort_by_cached_key(|movement| move_order.sort_key(&board, *movement, 1));
+        assert_eq!(
+            moves[..4],
+            [
+                "e4d5".parse().unwrap(),
+                "e4f5".parse().unwrap(),
+                castling,
+                killer,
+            ]
+        );
+    }
+}
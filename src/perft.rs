@@ -0,0 +1,189 @@
+use std::{
+    error::Error,
+    io::{self, BufRead, Write, stdin, stdout},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    board::Board,
+    fen::Fen,
+    misc::{extract_prefix_token, strip_prefix_token},
+};
+
+// The board is restored before returning
+pub fn perft(board: &mut Board, depth: u32) -> u64 {
+    perft_with_stop_signal(board, depth, &AtomicBool::new(false)).unwrap()
+}
+// None when stopped before finishing
+fn perft_with_stop_signal(board: &mut Board, depth: u32, stop_signal: &AtomicBool) -> Option<u64> {
+    match depth {
+        0 => Some(1),
+        1 => Some(board.legal_moves().count() as u64),
+        depth => {
+            if stop_signal.load(Ordering::Relaxed) {
+                return None;
+            }
+            board
+                .legal_moves()
+                .map(|movement| {
+                    let undo = board.make(movement);
+                    let nodes = perft_with_stop_signal(board, depth - 1, stop_signal);
+                    board.unmake(undo);
+                    nodes
+                })
+                .sum()
+        }
+    }
+}
+pub fn write_divide(output: &mut impl Write, board: &Board, depth: u32) -> io::Result<u64> {
+    let total = write_divide_with_stop_signal(output, board, depth, &AtomicBool::new(false))?;
+    Ok(total.unwrap())
+}
+// The moves finished before stopping are still written, the total is only
+// written and returned when every move is finished
+pub fn write_divide_with_stop_signal(
+    output: &mut impl Write,
+    board: &Board,
+    depth: u32,
+    stop_signal: &AtomicBool,
+) -> io::Result<Option<u64>> {
+    let mut walker = board.clone();
+    let mut total = 0;
+    for movement in board.legal_moves() {
+        let undo = walker.make(movement);
+        let nodes = perft_with_stop_signal(&mut walker, depth.saturating_sub(1), stop_signal);
+        walker.unmake(undo);
+        let Some(nodes) = nodes else {
+            return Ok(None);
+        };
+        writeln!(output, "{}: {nodes}", movement.as_lan())?;
+        total += nodes;
+    }
+    writeln!(output)?;
+    writeln!(output, "Nodes searched: {total}")?;
+    Ok(Some(total))
+}
+// Parses `<depth> [fen]`, the starting position is used when fen is omitted
+pub fn parse_depth_and_board(line: &str) -> Result<(u32, Board), Box<dyn Error>> {
+    let depth = extract_prefix_token(line).parse()?;
+    let fen = strip_prefix_token(line, extract_prefix_token(line)).unwrap_or_default();
+    let board = if fen.is_empty() {
+        Board::starting_position()
+    } else {
+        let fen: Fen = fen.parse()?;
+        fen.try_into()?
+    };
+    Ok((depth, board))
+}
+pub fn perft_loop() {
+    let input = stdin().lock();
+    let mut output = stdout().lock();
+    for line in input.lines() {
+        let line = line.unwrap();
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (depth, board) = match parse_depth_and_board(line) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                eprintln!("Error: {err}");
+                continue;
+            }
+        };
+        write_divide(&mut output, &board, depth).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{board::Board, fen::Fen, perft::perft};
+
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let fen: Fen = fen.parse().unwrap();
+        let mut board: Board = fen.try_into().unwrap();
+        for (depth, expected) in (1..).zip(expected.iter().copied()) {
+            assert_eq!(perft(&mut board, depth), expected, "{fen} at depth {depth}");
+        }
+    }
+    #[test]
+    fn starting_position() {
+        assert_perft(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[20, 400, 8_902, 197_281],
+        );
+    }
+    #[test]
+    fn kiwipete() {
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2_039, 97_862],
+        );
+    }
+    #[test]
+    fn position_3() {
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2_812, 43_238],
+        );
+    }
+    #[test]
+    fn position_4() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9_467],
+        );
+    }
+    #[test]
+    fn position_4_mirrored() {
+        assert_perft(
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            &[6, 264, 9_467],
+        );
+    }
+    #[test]
+    fn position_5() {
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1_486, 62_379],
+        );
+    }
+    #[test]
+    fn position_6() {
+        assert_perft(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P3/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            &[47, 1_845, 81_467],
+        );
+    }
+    #[test]
+    fn chess960() {
+        for (fen, expected) in [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                &[21, 528, 12_189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                &[21, 807, 18_002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                &[20, 479, 10_471],
+            ),
+            (
+                "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+                &[22, 593, 13_440],
+            ),
+            (
+                "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+                &[28, 1_120, 31_058],
+            ),
+        ] {
+            assert_perft(fen, expected);
+        }
+    }
+    #[test]
+    fn chess960_standard_starting_position() {
+        assert_eq!(perft(&mut Board::chess960(521), 3), 8_902);
+    }
+}
@@ -0,0 +1,504 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    iter::{from_fn, once},
+    str::FromStr,
+};
+
+use crate::{
+    board::{Board, InvalidBoard, Move},
+    color::Color,
+    end_state::{EndReason, EndState},
+    fen::{Fen, ParseFenError},
+    history::History,
+    san::ParseSanError,
+};
+
+const LINE_WIDTH: usize = 80;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pgn {
+    // Kept in order, the value of the `Result` tag is taken from `result`
+    pub tags: Vec<(String, String)>,
+    pub start: Board,
+    pub moves: Vec<Move>,
+    pub result: Option<EndState>,
+}
+impl Pgn {
+    pub fn new(start: Board, moves: Vec<Move>, result: Option<EndState>) -> Self {
+        let mut tags: Vec<_> = [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", format_result(result)),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect();
+        if let Some(end_state) = result {
+            // The exact reason is written as a comment after the moves
+            let termination = match end_state {
+                EndState::Win(_, EndReason::Adjudication)
+                | EndState::Draw(EndReason::Adjudication) => "adjudication",
+                _ => "normal",
+            };
+            tags.push(("Termination".to_owned(), termination.to_owned()));
+        }
+        let fen = start.as_fen();
+        if fen != Board::starting_position().as_fen() {
+            if !fen.uses_standard_castling() {
+                tags.push(("Variant".to_owned(), "Chess960".to_owned()));
+            }
+            tags.push(("SetUp".to_owned(), "1".to_owned()));
+            tags.push(("FEN".to_owned(), fen.to_string()));
+        }
+        Pgn {
+            tags,
+            start,
+            moves,
+            result,
+        }
+    }
+    // Parses the games one after another, stopping after the first error
+    pub fn parse_all(src: &str) -> impl Iterator<Item = Result<Pgn, ParsePgnError>> {
+        let mut rest = Some(src);
+        from_fn(move || {
+            let src = rest?.trim_start();
+            if src.is_empty() {
+                return None;
+            }
+            match parse_game(src) {
+                Ok((pgn, after)) => {
+                    rest = Some(after);
+                    Some(Ok(pgn))
+                }
+                Err(err) => {
+                    rest = None;
+                    Some(Err(err))
+                }
+            }
+        })
+    }
+    // Every position of the game including the starting position
+    pub fn boards(&self) -> impl Iterator<Item = Board> {
+        let mut board = self.start.clone();
+        once(self.start.clone()).chain(self.moves.iter().map(move |movement| {
+            board.move_piece(*movement);
+            board.clone()
+        }))
+    }
+}
+impl Display for Pgn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.tags {
+            let value = if name == "Result" {
+                format_result(self.result)
+            } else {
+                value
+            };
+            write!(f, "[{name} \"")?;
+            for c in value.chars() {
+                if matches!(c, '\\' | '"') {
+                    write!(f, "\\")?;
+                }
+                write!(f, "{c}")?;
+            }
+            writeln!(f, "\"]")?;
+        }
+        writeln!(f)?;
+        let mut line = String::new();
+        let mut push = |f: &mut Formatter<'_>, token: &str| {
+            if !line.is_empty() {
+                if line.len() + 1 + token.len() > LINE_WIDTH {
+                    writeln!(f, "{line}")?;
+                    line.clear();
+                } else {
+                    line.push(' ');
+                }
+            }
+            line.push_str(token);
+            Ok(())
+        };
+        let mut board = self.start.clone();
+        let mut full_move = board.as_fen().full_move;
+        for (i, movement) in self.moves.iter().enumerate() {
+            match board.current_player() {
+                Color::White => push(f, &format!("{full_move}."))?,
+                Color::Black if i == 0 => push(f, &format!("{full_move}..."))?,
+                Color::Black => (),
+            }
+            push(f, &movement.to_san(&board))?;
+            if board.current_player() == Color::Black {
+                full_move += 1;
+            }
+            board.move_piece(*movement);
+        }
+        if let Some(end_state @ (EndState::Win(_, reason) | EndState::Draw(reason))) = self.result
+            && reason != EndReason::Adjudication
+        {
+            push(f, &format!("{{{end_state}}}"))?;
+        }
+        push(f, format_result(self.result))?;
+        writeln!(f, "{line}")?;
+        Ok(())
+    }
+}
+impl FromStr for Pgn {
+    type Err = ParsePgnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pgn, rest) = parse_game(s)?;
+        if let Some(c) = rest.trim_start().chars().next() {
+            return Err(ParsePgnError::Unexpected(c));
+        }
+        Ok(pgn)
+    }
+}
+// Parses a single game, returning the text after it
+fn parse_game(s: &str) -> Result<(Pgn, &str), ParsePgnError> {
+    let mut tags = Vec::new();
+    let mut rest = s.trim_start();
+    while let Some(tag) = rest.strip_prefix('[') {
+        let (name, value, after) = parse_tag(tag)?;
+        tags.push((name, value));
+        rest = after.trim_start();
+    }
+    if let Some(variant) = find_tag(&tags, "Variant")
+        && !matches!(
+            variant.to_lowercase().as_str(),
+            "standard" | "chess" | "chess960" | "chess 960" | "fischerandom"
+        )
+    {
+        return Err(ParsePgnError::UnsupportedVariant(variant.to_owned()));
+    }
+    let start = match find_tag(&tags, "FEN") {
+        Some(fen) => fen.parse::<Fen>()?.try_into()?,
+        None => Board::starting_position(),
+    };
+    let mut outcome = find_tag(&tags, "Result")
+        .map(parse_outcome)
+        .transpose()?
+        .unwrap_or(Outcome::Unfinished);
+    if find_tag(&tags, "Result").is_none() {
+        tags.push(("Result".to_owned(), "*".to_owned()));
+    }
+    let mut board = start.clone();
+    let mut history = History::new();
+    let mut moves = Vec::new();
+    let mut depth: u32 = 0;
+    loop {
+        rest = rest.trim_start();
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+        match c {
+            '{' => {
+                let end = rest.find('}').ok_or(ParsePgnError::UnterminatedComment)?;
+                rest = &rest[end + 1..];
+            }
+            ';' => rest = rest.find('\n').map_or("", |i| &rest[i..]),
+            // The tags of the next game, this one is missing its result
+            '[' if depth == 0 => break,
+            '(' => {
+                depth += 1;
+                rest = &rest[1..];
+            }
+            ')' => {
+                depth = depth.checked_sub(1).ok_or(ParsePgnError::Unexpected(c))?;
+                rest = &rest[1..];
+            }
+            _ => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || matches!(c, '{' | ';' | '(' | ')'))
+                    .unwrap_or(rest.len());
+                let token = &rest[..end];
+                rest = &rest[end..];
+                // Moves inside variations are skipped
+                if depth > 0 || token.starts_with('$') {
+                    continue;
+                }
+                if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+                    outcome = parse_outcome(token)?;
+                    break;
+                }
+                // Move numbers may be directly followed by the move e.g. `1.e4`
+                let without_number = token.trim_start_matches(|c: char| c.is_ascii_digit());
+                let token = if without_number.starts_with('.') {
+                    without_number.trim_start_matches('.')
+                } else {
+                    token
+                };
+                if token.is_empty() {
+                    continue;
+                }
+                let movement = board
+                    .parse_san(token)
+                    .map_err(|err| ParsePgnError::San(token.to_owned(), err))?;
+                history.push(board.zobrist());
+                board.move_piece(movement);
+                moves.push(movement);
+            }
+        }
+    }
+    if depth > 0 {
+        return Err(ParsePgnError::UnterminatedVariation);
+    }
+    // Any termination other than `normal` means the game didn't end on the
+    // board
+    let end_state = match find_tag(&tags, "Termination") {
+        Some(termination) if !termination.eq_ignore_ascii_case("normal") => None,
+        _ => board.end_state_with_history(&history),
+    };
+    let result = outcome.with_reason(end_state);
+    let pgn = Pgn {
+        tags,
+        start,
+        moves,
+        result,
+    };
+    Ok((pgn, rest))
+}
+fn find_tag<'a>(tags: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|(tag, _)| tag == name)
+        .map(|(_, value)| value.as_str())
+}
+fn parse_tag(src: &str) -> Result<(String, String, &str), ParsePgnError> {
+    let src = src.trim_start();
+    let end = src
+        .find(|c: char| c.is_whitespace() || c == '"')
+        .ok_or(ParsePgnError::InvalidTag)?;
+    let name = src[..end].to_owned();
+    let mut chars = src[end..]
+        .trim_start()
+        .strip_prefix('"')
+        .ok_or(ParsePgnError::InvalidTag)?
+        .char_indices();
+    let mut value = String::new();
+    let rest = loop {
+        match chars.next().ok_or(ParsePgnError::InvalidTag)? {
+            (_, '\\') => value.push(chars.next().ok_or(ParsePgnError::InvalidTag)?.1),
+            (_, '"') => break chars.as_str(),
+            (_, c) => value.push(c),
+        }
+    };
+    let rest = rest
+        .trim_start()
+        .strip_prefix(']')
+        .ok_or(ParsePgnError::InvalidTag)?;
+    Ok((name, value, rest))
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Outcome {
+    Win(Color),
+    Draw,
+    Unfinished,
+}
+impl Outcome {
+    // The reason is taken from the final position when it agrees with the
+    // result, otherwise the game was decided outside of the board
+    fn with_reason(self, end_state: Option<EndState>) -> Option<EndState> {
+        match self {
+            Outcome::Win(color) => Some(match end_state {
+                Some(end_state @ EndState::Win(winner, _)) if winner == color => end_state,
+                _ => EndState::Win(color, EndReason::Adjudication),
+            }),
+            Outcome::Draw => Some(match end_state {
+                Some(end_state @ EndState::Draw(_)) => end_state,
+                _ => EndState::Draw(EndReason::Adjudication),
+            }),
+            Outcome::Unfinished => None,
+        }
+    }
+}
+fn parse_outcome(src: &str) -> Result<Outcome, ParsePgnError> {
+    match src {
+        "1-0" => Ok(Outcome::Win(Color::White)),
+        "0-1" => Ok(Outcome::Win(Color::Black)),
+        "1/2-1/2" => Ok(Outcome::Draw),
+        "*" => Ok(Outcome::Unfinished),
+        src => Err(ParsePgnError::InvalidResult(src.to_owned())),
+    }
+}
+fn format_result(result: Option<EndState>) -> &'static str {
+    match result {
+        Some(EndState::Win(Color::White, _)) => "1-0",
+        Some(EndState::Win(Color::Black, _)) => "0-1",
+        Some(EndState::Draw(_)) => "1/2-1/2",
+        None => "*",
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsePgnError {
+    InvalidTag,
+    ParseFenError(ParseFenError),
+    InvalidBoard(InvalidBoard),
+    UnsupportedVariant(String),
+    InvalidResult(String),
+    San(String, ParseSanError),
+    UnterminatedComment,
+    UnterminatedVariation,
+    Unexpected(char),
+}
+impl From<ParseFenError> for ParsePgnError {
+    fn from(value: ParseFenError) -> Self {
+        ParsePgnError::ParseFenError(value)
+    }
+}
+impl From<InvalidBoard> for ParsePgnError {
+    fn from(value: InvalidBoard) -> Self {
+        ParsePgnError::InvalidBoard(value)
+    }
+}
+impl Display for ParsePgnError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsePgnError::InvalidTag => write!(f, "invalid tag pair")?,
+            ParsePgnError::ParseFenError(err) => write!(f, "{err}")?,
+            ParsePgnError::InvalidBoard(err) => write!(f, "{err}")?,
+            ParsePgnError::UnsupportedVariant(variant) => {
+                write!(f, "unsupported variant `{variant}`")?;
+            }
+            ParsePgnError::InvalidResult(result) => write!(f, "invalid result `{result}`")?,
+            ParsePgnError::San(san, err) => write!(f, "`{san}`: {err}")?,
+            ParsePgnError::UnterminatedComment => write!(f, "unterminated comment")?,
+            ParsePgnError::UnterminatedVariation => write!(f, "unterminated variation")?,
+            ParsePgnError::Unexpected(c) => write!(f, "unexpected `{c}`")?,
+        }
+        Ok(())
+    }
+}
+impl Error for ParsePgnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParsePgnError::ParseFenError(err) => Some(err),
+            ParsePgnError::InvalidBoard(err) => Some(err),
+            ParsePgnError::San(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{
+        board::Board,
+        color::Color,
+        end_state::{EndReason, EndState},
+        fen::Fen,
+        pgn::{ParsePgnError, Pgn, find_tag},
+    };
+
+    const GAME: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 {This opening is called the Ruy Lopez.}
+4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7
+11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
+Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
+23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
+hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
+35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
+Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+"#;
+
+    #[test]
+    fn parse_game() {
+        let pgn: Pgn = GAME.parse().unwrap();
+        assert_eq!(find_tag(&pgn.tags, "White"), Some("Fischer, Robert J."));
+        assert_eq!(pgn.moves.len(), 85);
+        assert_eq!(pgn.result, Some(EndState::Draw(EndReason::Adjudication)));
+        assert_eq!(
+            pgn.boards().last().unwrap().as_fen().to_string(),
+            "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43",
+        );
+    }
+    #[test]
+    fn round_trip() {
+        let pgn: Pgn = GAME.parse().unwrap();
+        let written = pgn.to_string();
+        assert!(written.lines().all(|line| line.len() <= 80));
+        assert_eq!(written.parse(), Ok(pgn));
+    }
+    #[test]
+    fn comments_nags_and_variations() {
+        let pgn: Pgn = "1.e4 $1 {best by test} e5 (1...c5 2.Nf3 (2.c3) d6) ; rest of line\n\
+            2.Nf3! 2...Nc6?! 3.Bb5 *"
+            .parse()
+            .unwrap();
+        assert_eq!(pgn.moves.len(), 5);
+        assert_eq!(pgn.result, None);
+        assert_eq!(
+            "1. e4 (1... c5".parse::<Pgn>(),
+            Err(ParsePgnError::UnterminatedVariation),
+        );
+    }
+    #[test]
+    fn multiple_games() {
+        let src = format!("{GAME}\n[Result \"1-0\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n1. d4 d5 *\n");
+        let games: Vec<_> = Pgn::parse_all(&src).collect::<Result<_, _>>().unwrap();
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0], GAME.parse().unwrap());
+        assert_eq!(games[1].moves.len(), 4);
+        assert_eq!(
+            games[1].result,
+            Some(EndState::Win(Color::Black, EndReason::Checkmate))
+        );
+        assert_eq!(games[2].moves.len(), 2);
+        assert_eq!(games[2].result, None);
+        assert_eq!(src.parse::<Pgn>(), Err(ParsePgnError::Unexpected('[')));
+    }
+    #[test]
+    fn termination() {
+        let start: Board = "k7/8/8/1Q6/8/8/8/K7 w - - 0 1"
+            .parse::<Fen>()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let moves = vec![start.parse_san("Qb6").unwrap()];
+        for (result, termination) in [
+            (EndState::Draw(EndReason::Stalemate), "normal"),
+            (EndState::Draw(EndReason::Adjudication), "adjudication"),
+        ] {
+            let pgn = Pgn::new(start.clone(), moves.clone(), Some(result));
+            assert_eq!(find_tag(&pgn.tags, "Termination"), Some(termination));
+            assert_eq!(pgn.to_string().parse(), Ok(pgn));
+        }
+    }
+    #[test]
+    fn chess960_setup() {
+        let start = Board::chess960(0);
+        let movement = start.parse_san("Nc3").unwrap();
+        let pgn = Pgn::new(
+            start.clone(),
+            vec![movement],
+            Some(EndState::Win(Color::Black, EndReason::Adjudication)),
+        );
+        assert_eq!(find_tag(&pgn.tags, "Variant"), Some("Chess960"));
+        let parsed: Pgn = pgn.to_string().parse().unwrap();
+        assert_eq!(parsed.start, start);
+        assert_eq!(parsed.moves, [movement]);
+        assert_eq!(
+            parsed.result,
+            Some(EndState::Win(Color::Black, EndReason::Adjudication))
+        );
+    }
+    #[test]
+    fn black_to_move() {
+        let start: Board = "4k3/8/8/8/8/8/8/R3K3 b Q - 0 30"
+            .parse::<Fen>()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let moves = vec![start.parse_san("Kd7").unwrap()];
+        let pgn = Pgn::new(start, moves, None);
+        assert!(pgn.to_string().contains("\n\n30... Kd7 *\n"));
+    }
+}
@@ -2,24 +2,32 @@ use rand::random_range;
 use rustc_hash::FxHashSet;
 
 use crate::{
-    board::{Board, Lan, ParseLanError},
+    board::{Board, Lan, Move, ParseLanError},
     board_display::BoardDisplay,
     color::Color,
     coord::Coord,
     fen::{Fen, ParseFenError},
-    game_tree::{GameTree, Table},
+    game_tree::{GameTree, SearchOptions, Table},
+    history::History,
     misc::{MEBIBYTES, strip_prefix_token},
+    perft::write_divide,
+    pgn::Pgn,
 };
 use std::{
     collections::HashSet,
     error::Error,
     fmt::{self, Display, Formatter, Write as _},
+    fs::{read_to_string, write},
     io::{BufRead, Write, stderr, stdin, stdout},
     num::ParseIntError,
     str::FromStr,
+    time::Instant,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// small enough to allocate instantly, the searches here are short anyway
+const TABLE_SIZE: usize = 16 * MEBIBYTES;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum Input {
     Help,
     Flip,
@@ -28,10 +36,14 @@ enum Input {
     Quit,
     Import(Fen),
     ExportFen,
+    Save(String),
+    Load(String),
     Coord(Coord),
     Move(Lan),
     Bot(u32),
     CheckPrune(u32),
+    CheckScaling(u32),
+    Perft(u32),
     Eval,
 }
 impl Display for Input {
@@ -44,10 +56,14 @@ impl Display for Input {
             Input::Quit => write!(f, "quit")?,
             Input::Import(fen) => write!(f, "import {fen}")?,
             Input::ExportFen => write!(f, "fen")?,
+            Input::Save(path) => write!(f, "save {path}")?,
+            Input::Load(path) => write!(f, "load {path}")?,
             Input::Coord(position) => write!(f, "{position}")?,
             Input::Move(movement) => write!(f, "{movement}")?,
             Input::Bot(depth) => write!(f, "bot {depth}")?,
             Input::CheckPrune(depth) => write!(f, "check prune {depth}")?,
+            Input::CheckScaling(depth) => write!(f, "check scaling {depth}")?,
+            Input::Perft(depth) => write!(f, "perft {depth}")?,
             Input::Eval => write!(f, "eval")?,
         }
         Ok(())
@@ -68,10 +84,18 @@ impl FromStr for Input {
             s => {
                 if let Some(s) = strip_prefix_token(s, "import") {
                     Ok(Input::Import(s.parse()?))
+                } else if let Some(s) = strip_prefix_token(s, "save") {
+                    Ok(Input::Save(s.to_owned()))
+                } else if let Some(s) = strip_prefix_token(s, "load") {
+                    Ok(Input::Load(s.to_owned()))
                 } else if let Some(s) = strip_prefix_token(s, "bot") {
                     Ok(Input::Bot(s.parse()?))
                 } else if let Some(s) = strip_prefix_token(s, "check prune") {
                     Ok(Input::CheckPrune(s.parse()?))
+                } else if let Some(s) = strip_prefix_token(s, "check scaling") {
+                    Ok(Input::CheckScaling(s.parse()?))
+                } else if let Some(s) = strip_prefix_token(s, "perft") {
+                    Ok(Input::Perft(s.parse()?))
                 } else if let Ok(position) = s.parse() {
                     Ok(Input::Coord(position))
                 } else {
@@ -99,21 +123,31 @@ pub fn repl() {
     let mut update = true;
     let mut view = Color::White;
     let mut first_time = true;
+    let mut history = History::new();
+    let mut last_move = None;
+    let mut start = board.clone();
+    let mut moves = Vec::new();
     let mut game_tree = GameTree::new(board.clone());
-    let mut table = Table::new(4096 * MEBIBYTES / Table::ELEMENT_SIZE);
+    let mut table = Table::new(TABLE_SIZE);
     let thread = 8;
     loop {
         if update {
             valid_moves.clear();
             info.clear();
-            match board.valid_moves() {
-                Ok(moves) => {
-                    valid_moves.extend(moves.flat_map(|movement| movement.as_lan_iter(&board)));
-                    writeln!(&mut info, "{} plays", board.current_player()).unwrap();
-                }
-                Err(end_state) => {
-                    writeln!(&mut info, "{end_state}").unwrap();
-                }
+            if let Some(last_move) = &last_move {
+                writeln!(&mut info, "last move: {last_move}").unwrap();
+            }
+            if let Some(end_state) = board.end_state_with_history(&history) {
+                writeln!(&mut info, "{end_state}").unwrap();
+            } else {
+                valid_moves.extend(
+                    board
+                        .valid_moves()
+                        .into_iter()
+                        .flatten()
+                        .flat_map(Move::as_lan_iter),
+                );
+                writeln!(&mut info, "{} plays", board.current_player()).unwrap();
             }
         }
         if first_time {
@@ -138,7 +172,17 @@ pub fn repl() {
             output.flush().unwrap();
             let text = lines.next().unwrap().unwrap();
             let input = match text.trim().parse() {
+                // Pawn pushes in SAN look like coordinates
+                Ok(Input::Coord(position))
+                    if board.index(position).is_none()
+                        && let Ok(movement) = board.parse_san(&text) =>
+                {
+                    Input::Move(movement.as_lan())
+                }
                 Ok(input) => input,
+                Err(_) if let Ok(movement) = board.parse_san(&text) => {
+                    Input::Move(movement.as_lan())
+                }
                 Err(err) => {
                     writeln!(error, "Error: {err}").unwrap();
                     writeln!(error, "for available command, enter `help`").unwrap();
@@ -154,55 +198,114 @@ pub fn repl() {
                     writeln!(output, "restart        - reset to starting position").unwrap();
                     writeln!(output, "start chess960 - start a new chess960 game").unwrap();
                     writeln!(output, "import <fen>   - import a position").unwrap();
+                    writeln!(output, "save <file>    - save the game as pgn").unwrap();
+                    writeln!(output, "load <file>    - load a game from pgn").unwrap();
                     writeln!(output).unwrap();
                     writeln!(output, "e2             - view valid moves").unwrap();
                     writeln!(output, "e2e4           - play the move").unwrap();
                     writeln!(output, "e7e8q          - move and promote").unwrap();
                     writeln!(output, "e1g1 (or e1h1) - perform castling").unwrap();
+                    writeln!(output, "Nf3, O-O, e8=Q - play the move in SAN").unwrap();
                     writeln!(output).unwrap();
-                    writeln!(output, "bot <depth>         - let a bot play").unwrap();
-                    writeln!(output, "check prune <depth> - check pruning performance").unwrap();
-                    writeln!(output, "eval                - evaluate position at depth 0").unwrap();
+                    writeln!(output, "bot <depth>           - let a bot play").unwrap();
+                    writeln!(output, "check prune <depth>   - check pruning performance").unwrap();
+                    writeln!(
+                        output,
+                        "check scaling <depth> - check multithreading performance"
+                    )
+                    .unwrap();
+                    writeln!(
+                        output,
+                        "eval                  - evaluate position at depth 0"
+                    )
+                    .unwrap();
+                    writeln!(output, "perft <depth>         - count leaf nodes per move").unwrap();
                 }
                 Input::Flip => {
                     view = !view;
                 }
                 Input::Restart => {
                     board = Board::starting_position();
+                    history.clear();
                     game_tree = GameTree::new(board.clone());
                     update = true;
                     highlighted.clear();
+                    last_move = None;
+                    start = board.clone();
+                    moves.clear();
                 }
                 Input::StartChess960 => {
                     board = Board::chess960(random_range(0..960));
+                    history.clear();
                     game_tree = GameTree::new(board.clone());
                     update = true;
                     highlighted.clear();
+                    last_move = None;
+                    start = board.clone();
+                    moves.clear();
                 }
                 Input::Quit => return,
                 Input::Import(fen) => {
-                    board = match fen.board.try_into() {
+                    board = match fen.try_into() {
                         Ok(board) => board,
                         Err(err) => {
                             writeln!(error, "Error: {err}").unwrap();
                             continue;
                         }
                     };
+                    history.clear();
                     game_tree = GameTree::new(board.clone());
                     update = true;
                     highlighted.clear();
+                    last_move = None;
+                    start = board.clone();
+                    moves.clear();
                 }
                 Input::ExportFen => {
-                    writeln!(
-                        output,
-                        "{}",
-                        Fen {
-                            board: board.as_hashable(),
-                            half_move: 0,
-                            full_move: 1
+                    writeln!(output, "{}", board.as_fen()).unwrap();
+                }
+                Input::Save(path) => {
+                    let pgn = Pgn::new(
+                        start.clone(),
+                        moves.clone(),
+                        board.end_state_with_history(&history),
+                    );
+                    if let Err(err) = write(&path, pgn.to_string()) {
+                        writeln!(error, "Error: {err}").unwrap();
+                        continue;
+                    }
+                }
+                Input::Load(path) => {
+                    // The first game is loaded when the file has more
+                    let pgn = match read_to_string(&path) {
+                        Ok(src) => match Pgn::parse_all(&src).next() {
+                            Some(Ok(pgn)) => pgn,
+                            Some(Err(err)) => {
+                                writeln!(error, "Error: {err}").unwrap();
+                                continue;
+                            }
+                            None => {
+                                writeln!(error, "Error: no game found").unwrap();
+                                continue;
+                            }
+                        },
+                        Err(err) => {
+                            writeln!(error, "Error: {err}").unwrap();
+                            continue;
                         }
-                    )
-                    .unwrap();
+                    };
+                    history.clear();
+                    last_move = None;
+                    for (board, movement) in pgn.boards().zip(&pgn.moves) {
+                        last_move = Some(movement.to_san(&board));
+                        history.push(board.zobrist());
+                    }
+                    board = pgn.boards().last().unwrap();
+                    game_tree = GameTree::with_history(board.clone(), history.clone());
+                    update = true;
+                    highlighted.clear();
+                    start = pgn.start;
+                    moves = pgn.moves;
                 }
                 Input::Coord(position) => {
                     if let Some(piece) = board.index(position) {
@@ -229,32 +332,133 @@ pub fn repl() {
                         writeln!(error, "Error: {lan} is an invalid move").unwrap();
                         continue;
                     };
-                    board.move_lan(*movement);
-                    game_tree.move_piece(*movement);
+                    let movement = movement.as_move(&board);
+                    last_move = Some(movement.to_san(&board));
+                    moves.push(movement);
+                    history.push(board.zobrist());
+                    board.move_piece(movement);
+                    game_tree.move_piece(lan);
                     highlighted.clear();
                     highlighted.push(lan.origin);
                     highlighted.push(lan.destination);
                     update = true;
                 }
                 Input::Bot(depth) => {
-                    table.clear_allocation();
+                    table.clear();
                     game_tree.calculate(depth, &mut table, thread);
                     let movement = game_tree.best_move().unwrap();
-                    board.move_lan(movement);
-                    game_tree.move_piece(movement);
+                    let lan = movement;
+                    let movement = lan.as_move(&board);
+                    last_move = Some(movement.to_san(&board));
+                    moves.push(movement);
+                    history.push(board.zobrist());
+                    board.move_piece(movement);
+                    game_tree.move_piece(lan);
                     highlighted.clear();
-                    highlighted.push(movement.origin);
-                    highlighted.push(movement.destination);
+                    highlighted.push(lan.origin);
+                    highlighted.push(lan.destination);
                     update = true;
                 }
                 Input::CheckPrune(depth) => {
-                    game_tree = GameTree::new(board.clone());
+                    game_tree = GameTree::with_history(board.clone(), history.clone());
                     let total = game_tree.generate(depth);
+
+                    // the table is cleared before every search so the node
+                    // counts don't measure hits from the previous ones
+                    table.clear();
                     let presorted = game_tree.calculate(depth, &mut table, thread);
+                    table.clear();
                     let sorted = game_tree.calculate(depth, &mut table, thread);
                     writeln!(output, "total number of nodes: {total}").unwrap();
                     writeln!(output, "searched nodes with unsorted branches: {presorted}").unwrap();
                     writeln!(output, "searched nodes with sorted branches: {sorted}").unwrap();
+                    let default = SearchOptions::default();
+                    for (name, options) in [
+                        (
+                            "null-move pruning",
+                            SearchOptions {
+                                null_move_pruning: false,
+                                ..default
+                            },
+                        ),
+                        (
+                            "late move reductions",
+                            SearchOptions {
+                                late_move_reductions: false,
+                                ..default
+                            },
+                        ),
+                        (
+                            "check extensions",
+                            SearchOptions {
+                                check_extensions: false,
+                                ..default
+                            },
+                        ),
+                        (
+                            "move ordering heuristics",
+                            SearchOptions {
+                                move_ordering: false,
+                                ..default
+                            },
+                        ),
+                        (
+                            "principal variation search",
+                            SearchOptions {
+                                principal_variation_search: false,
+                                ..default
+                            },
+                        ),
+                        (
+                            "aspiration windows",
+                            SearchOptions {
+                                aspiration_windows: false,
+                                ..default
+                            },
+                        ),
+                        ("any of these", SearchOptions::UNPRUNED),
+                    ] {
+                        let mut game_tree = GameTree::with_history(board.clone(), history.clone());
+                        game_tree.set_options(options);
+                        game_tree.generate(depth);
+                        table.clear();
+                        let presorted = game_tree.calculate(depth, &mut table, thread);
+                        table.clear();
+                        let sorted = game_tree.calculate(depth, &mut table, thread);
+                        writeln!(output, "without {name}:").unwrap();
+                        writeln!(
+                            output,
+                            "  searched nodes with unsorted branches: {presorted}"
+                        )
+                        .unwrap();
+                        writeln!(output, "  searched nodes with sorted branches: {sorted}")
+                            .unwrap();
+                    }
+                }
+                Input::CheckScaling(depth) => {
+                    for thread in [1, 2, 4, 8] {
+                        let mut game_tree = GameTree::with_history(board.clone(), history.clone());
+                        table.clear();
+                        let start = Instant::now();
+                        let nodes: u64 = (1..=depth)
+                            .map(|depth| u64::from(game_tree.calculate(depth, &mut table, thread)))
+                            .sum();
+                        let time = start.elapsed();
+                        #[allow(
+                            clippy::cast_possible_truncation,
+                            clippy::cast_sign_loss,
+                            clippy::cast_precision_loss
+                        )]
+                        let nps = (nodes as f64 / time.as_secs_f64()) as u64;
+                        writeln!(
+                            output,
+                            "{thread} threads: depth {depth} in {time:?}, {nodes} nodes, {nps} nps"
+                        )
+                        .unwrap();
+                    }
+                }
+                Input::Perft(depth) => {
+                    write_divide(&mut output, &board, depth).unwrap();
                 }
                 Input::Eval => {
                     writeln!(output, "{:#?}", board.estimate()).unwrap();
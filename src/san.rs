@@ -0,0 +1,283 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    board::{Board, Lan, Move},
+    coord::{Coord, ParseCoordError},
+    end_state::{EndReason, EndState},
+    piece::PieceKind,
+};
+
+impl Move {
+    pub fn to_san(self, board: &Board) -> String {
+        let (lan, _) = self.as_lan_pair();
+        let mut san = String::new();
+        if self.is_castling() {
+            // Both "king to king's destination" and "king takes rook" point
+            // towards the rook
+            if lan.destination.x() > lan.origin.x() {
+                san.push_str("O-O");
+            } else {
+                san.push_str("O-O-O");
+            }
+        } else {
+            let piece = board.index(lan.origin).expect("piece not found").piece();
+            let capture = board.index(lan.destination).is_some()
+                || (piece == PieceKind::Pawn && lan.origin.x() != lan.destination.x());
+            if piece == PieceKind::Pawn {
+                if capture {
+                    san.push(lan.origin.file());
+                }
+            } else {
+                san.push(piece.uppercase());
+                let others: Vec<_> = board
+                    .legal_moves()
+                    .filter(|movement| !movement.is_castling())
+                    .map(|movement| movement.as_lan_pair().0)
+                    .filter(|other| {
+                        other.destination == lan.destination
+                            && other.origin != lan.origin
+                            && board.index(other.origin).unwrap().piece() == piece
+                    })
+                    .collect();
+                if !others.is_empty() {
+                    if others
+                        .iter()
+                        .all(|other| other.origin.x() != lan.origin.x())
+                    {
+                        san.push(lan.origin.file());
+                    } else if others
+                        .iter()
+                        .all(|other| other.origin.y() != lan.origin.y())
+                    {
+                        san.push(lan.origin.rank());
+                    } else {
+                        san.push(lan.origin.file());
+                        san.push(lan.origin.rank());
+                    }
+                }
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push(lan.destination.file());
+            san.push(lan.destination.rank());
+            if let Some(promotion) = lan.promotion {
+                san.push('=');
+                san.push(promotion.uppercase());
+            }
+        }
+        let board = board.clone_and_move(self);
+        if let Some(EndState::Win(_, EndReason::Checkmate)) = board.end_state() {
+            san.push('#');
+        } else if board.is_check() {
+            san.push('+');
+        }
+        san
+    }
+}
+impl Board {
+    // Besides proper SAN, this accepts missing or extra capture and check
+    // markers, over-specified origins, lowercase piece letters other than `b`,
+    // promotions without `=`, zeros in castling, and LAN
+    pub fn parse_san(&self, san: &str) -> Result<Move, ParseSanError> {
+        let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        let san = san.strip_suffix("e.p.").unwrap_or(san).trim_end();
+        let kingside = match san {
+            "O-O" | "0-0" | "o-o" => Some(true),
+            "O-O-O" | "0-0-0" | "o-o-o" => Some(false),
+            _ => None,
+        };
+        if let Some(kingside) = kingside {
+            return self.find_move(|movement| {
+                let (lan, _) = movement.as_lan_pair();
+                movement.is_castling() && (lan.destination.x() > lan.origin.x()) == kingside
+            });
+        }
+        if let Ok(lan) = san.parse::<Lan>() {
+            return self.find_move(|movement| movement.as_lan_iter().any(|other| other == lan));
+        }
+        let mut chars: Vec<_> = san
+            .chars()
+            .filter(|c| !matches!(c, 'x' | ':' | '-' | '='))
+            .collect();
+
+        let piece = match chars.first() {
+            Some('b') | None => PieceKind::Pawn,
+            Some(&c) if !c.is_ascii_digit() && !('a'..='h').contains(&c) => {
+                chars.remove(0);
+                PieceKind::from_fen(c).map_err(|_| ParseSanError::Unexpected(c))?
+            }
+            Some(_) => PieceKind::Pawn,
+        };
+        let promotion = match chars.last() {
+            Some(&c) if c.is_ascii_alphabetic() => {
+                chars.pop();
+                let promotion = PieceKind::from_fen(c).map_err(|_| ParseSanError::Unexpected(c))?;
+                Some(promotion)
+            }
+            _ => None,
+        };
+        let [origin @ .., x, y] = chars.as_slice() else {
+            return Err(ParseSanError::InvalidChar);
+        };
+        let destination = Coord::from_chars(*x, *y)?;
+        let mut origin_x = None;
+        let mut origin_y = None;
+        for &c in origin {
+            match c {
+                'a'..='h' if origin_x.is_none() => origin_x = Some(c),
+                '1'..='8' if origin_y.is_none() => origin_y = Some(c),
+                c => return Err(ParseSanError::Unexpected(c)),
+            }
+        }
+        self.find_move(|movement| {
+            let (lan, _) = movement.as_lan_pair();
+            !movement.is_castling()
+                && lan.destination == destination
+                && self.index(lan.origin).unwrap().piece() == piece
+                && origin_x.is_none_or(|x| lan.origin.file() == x)
+                && origin_y.is_none_or(|y| lan.origin.rank() == y)
+                && promotion.is_none_or(|promotion| lan.promotion == Some(promotion))
+        })
+    }
+    fn find_move(&self, mut predicate: impl FnMut(Move) -> bool) -> Result<Move, ParseSanError> {
+        let mut moves = self.legal_moves().filter(|movement| predicate(*movement));
+        let movement = moves.next().ok_or(ParseSanError::IllegalMove)?;
+        if moves.next().is_some() {
+            return Err(ParseSanError::AmbiguousMove);
+        }
+        Ok(movement)
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseSanError {
+    InvalidChar,
+    ParseCoordError(ParseCoordError),
+    Unexpected(char),
+    IllegalMove,
+    AmbiguousMove,
+}
+impl From<ParseCoordError> for ParseSanError {
+    fn from(value: ParseCoordError) -> Self {
+        ParseSanError::ParseCoordError(value)
+    }
+}
+impl Display for ParseSanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSanError::InvalidChar => write!(f, "provided string contains invalid character")?,
+            ParseSanError::ParseCoordError(err) => write!(f, "{err}")?,
+            ParseSanError::Unexpected(c) => write!(f, "unexpected `{c}`")?,
+            ParseSanError::IllegalMove => write!(f, "no legal move matches")?,
+            ParseSanError::AmbiguousMove => write!(f, "more than 1 legal moves match")?,
+        }
+        Ok(())
+    }
+}
+impl Error for ParseSanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseSanError::ParseCoordError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    use crate::{board::Board, fen::Fen, san::ParseSanError};
+
+    fn from_fen(fen: &str) -> Board {
+        let fen: Fen = fen.parse().unwrap();
+        fen.board.try_into().unwrap()
+    }
+    fn san(board: &Board, lan: &str) -> String {
+        board.parse_san(lan).unwrap().to_san(board)
+    }
+    #[test]
+    fn pieces_and_pawns() {
+        let board = Board::starting_position();
+        assert_eq!(san(&board, "g1f3"), "Nf3");
+        assert_eq!(san(&board, "e2e4"), "e4");
+        let board = from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+        assert_eq!(san(&board, "e4d5"), "exd5");
+    }
+    #[test]
+    fn disambiguation() {
+        let board = from_fen("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1");
+        assert_eq!(san(&board, "a1d1"), "Rad1");
+        let board = from_fen("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1");
+        assert_eq!(san(&board, "a1a4"), "R1a4");
+        let board = from_fen("4k3/8/8/8/8/Q1Q5/8/Q3K3 w - - 0 1");
+        assert_eq!(san(&board, "a3b2"), "Qa3b2");
+    }
+    #[test]
+    fn promotion_check_and_mate() {
+        let board = from_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(san(&board, "e7e8q"), "e8=Q+");
+        assert_eq!(san(&board, "e7e8n"), "e8=N");
+        let board = from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1");
+        assert_eq!(san(&board, "h1h8"), "Rh8#");
+    }
+    #[test]
+    fn castling() {
+        let board = from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(san(&board, "e1g1"), "O-O");
+        assert_eq!(san(&board, "e1c1"), "O-O-O");
+        let board = from_fen("1r4kr/8/8/8/8/8/8/1R4KR w KQkq - 0 1");
+        assert_eq!(san(&board, "g1h1"), "O-O");
+        assert_eq!(san(&board, "g1b1"), "O-O-O");
+    }
+    #[test]
+    fn sloppy() {
+        let board = from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+        let exd5 = board.parse_san("exd5").unwrap();
+        for sloppy in ["ed5", "e4xd5", "e4d5", "exd5+", "exd5!?", "Pxd5"] {
+            assert_eq!(board.parse_san(sloppy), Ok(exd5));
+        }
+        let nf3 = board.parse_san("Nf3").unwrap();
+        for sloppy in ["nf3", "Ng1f3", "Ng1-f3", "Ngf3", "N1f3"] {
+            assert_eq!(board.parse_san(sloppy), Ok(nf3));
+        }
+        let board = from_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(board.parse_san("e8Q"), board.parse_san("e8=Q"));
+        assert_eq!(board.parse_san("e8q"), board.parse_san("e8=Q"));
+        assert_eq!(board.parse_san("e8"), Err(ParseSanError::AmbiguousMove));
+        let board = from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(board.parse_san("0-0"), board.parse_san("O-O"));
+        assert_eq!(board.parse_san("e1h1"), board.parse_san("O-O"));
+    }
+    #[test]
+    fn illegal_and_ambiguous() {
+        let board = from_fen("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1");
+        assert_eq!(board.parse_san("Rd1"), Err(ParseSanError::AmbiguousMove));
+        assert_eq!(board.parse_san("Rd2"), Err(ParseSanError::IllegalMove));
+        assert_eq!(board.parse_san("O-O"), Err(ParseSanError::IllegalMove));
+    }
+    #[test]
+    fn round_trip() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut board = Board::chess960(rng.random_range(0..960));
+            for _ in 0..200 {
+                let moves: Vec<_> = board.legal_moves().collect();
+                if moves.is_empty() {
+                    break;
+                }
+                for movement in &moves {
+                    assert_eq!(
+                        board.parse_san(&movement.to_san(&board)),
+                        Ok(*movement),
+                        "{}",
+                        board.as_fen(),
+                    );
+                }
+                board.move_piece(moves[rng.random_range(0..moves.len())]);
+            }
+        }
+    }
+}
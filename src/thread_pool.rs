@@ -0,0 +1,63 @@
+use std::{
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::{
+        LazyLock, Mutex,
+        mpsc::{Receiver, Sender, channel},
+    },
+    thread::Builder,
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Threads are kept alive between searches so starting a search doesn't pay
+// for spawning them. Each worker runs its jobs in order.
+static WORKERS: LazyLock<Mutex<Vec<Sender<Job>>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+// Runs the job on the worker numbered `index`, spawning workers as needed.
+// The result can be waited on with the returned receiver.
+pub fn execute<T>(index: usize, job: impl FnOnce() -> T + Send + 'static) -> Receiver<T>
+where
+    T: Send + 'static,
+{
+    let (sender, receiver) = channel();
+    let job: Job = Box::new(move || {
+        // the result may be unwanted
+        let _ = sender.send(job());
+    });
+    let mut workers = WORKERS.lock().unwrap();
+    while workers.len() <= index {
+        let (sender, receiver) = channel::<Job>();
+        Builder::new()
+            .name(format!("search worker {}", workers.len()))
+            .spawn(move || {
+                for job in receiver {
+                    // a panicking job shouldn't take the worker with it, the
+                    // panic reaches the caller as a disconnected receiver
+                    let _ = catch_unwind(AssertUnwindSafe(job));
+                }
+            })
+            .unwrap();
+        workers.push(sender);
+    }
+    workers[index].send(job).unwrap();
+    receiver
+}
+#[cfg(test)]
+mod test {
+    use crate::thread_pool::execute;
+
+    #[test]
+    fn reuses_workers() {
+        let name = || std::thread::current().name().map(str::to_owned);
+        let first = execute(1, name).recv().unwrap();
+        let second = execute(1, name).recv().unwrap();
+        assert_eq!(first, Some("search worker 1".to_owned()));
+        assert_eq!(first, second);
+        assert_eq!(execute(0, || 1 + 1).recv().unwrap(), 2);
+    }
+    #[test]
+    fn survives_panicking_jobs() {
+        assert!(execute(7, || panic!("job panicked")).recv().is_err());
+        assert_eq!(execute(7, || 1 + 1).recv().unwrap(), 2);
+    }
+}
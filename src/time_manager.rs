@@ -0,0 +1,271 @@
+use std::{
+    cmp::min,
+    time::{Duration, Instant},
+};
+
+use crate::{board::Lan, color::Color, game_tree::GameTree, heuristics::Score};
+
+// the hard limit is at most this many times the soft limit
+const HARD_LIMIT_RATIO: u32 = 4;
+
+// scales the soft limit by how many iterations in a row found the same best
+// move, a move that keeps changing deserves more time
+const STABILITY_SCALE: [f32; 5] = [2.0, 1.5, 1.2, 1.0, 0.7];
+
+// in centipawns, from the perspective of the current player
+const SCORE_DROP: i32 = 50;
+const SCORE_DROP_SCALE: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimeLimit {
+    // the time the search aims for, it may finish earlier or later depending
+    // on how the search goes. None when the hard limit should be used fully.
+    pub soft: Option<Duration>,
+    // the search is stopped at this point no matter what
+    pub hard: Duration,
+}
+impl TimeLimit {
+    // Exactly `move_time` minus the time lost communicating with the GUI
+    pub fn fixed(move_time: Duration, move_overhead: Duration) -> Self {
+        TimeLimit {
+            soft: None,
+            hard: move_time.saturating_sub(move_overhead),
+        }
+    }
+    // A share of the remaining time, never more than half of it for the soft
+    // limit and three quarters for the hard limit
+    pub fn from_clock(
+        time: Duration,
+        increment: Duration,
+        moves_to_go: f32,
+        move_overhead: Duration,
+    ) -> Self {
+        let available = time.saturating_sub(move_overhead);
+        let soft = min(available.div_f32(moves_to_go) + increment, available / 2);
+        TimeLimit {
+            soft: Some(soft),
+            hard: min(soft * HARD_LIMIT_RATIO, available * 3 / 4),
+        }
+    }
+}
+// Consulted by the engine between iterations, while the hard limit is enforced
+// during iterations through the stop signal
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    start: Instant,
+    limit: TimeLimit,
+    best_move: Option<Lan>,
+    stable_iterations: usize,
+    score: Option<Score>,
+}
+impl TimeManager {
+    pub fn new(limit: TimeLimit) -> Self {
+        TimeManager {
+            start: Instant::now(),
+            limit,
+            best_move: None,
+            stable_iterations: 0,
+            score: None,
+        }
+    }
+    pub fn hard_limit(&self) -> Duration {
+        self.limit.hard
+    }
+    // Called after every completed iteration, returns whether the next one is
+    // worth starting
+    pub fn should_continue(&mut self, game_tree: &GameTree) -> bool {
+        self.should_continue_after(
+            self.start.elapsed(),
+            game_tree.current_player(),
+            game_tree.best_move(),
+            game_tree.score(),
+        )
+    }
+    fn should_continue_after(
+        &mut self,
+        elapsed: Duration,
+        current_player: Option<Color>,
+        best_move: Option<Lan>,
+        score: Option<Score>,
+    ) -> bool {
+        if best_move == self.best_move {
+            self.stable_iterations += 1;
+        } else {
+            self.stable_iterations = 0;
+        }
+        let dropped = match (current_player, self.score, score) {
+            (Some(current_player), Some(previous), Some(score)) => {
+                score_dropped(current_player, previous, score)
+            }
+            _ => false,
+        };
+        self.best_move = best_move;
+        self.score = score;
+        let Some(soft) = self.limit.soft else {
+            return elapsed < self.limit.hard;
+        };
+        let stability = STABILITY_SCALE[min(self.stable_iterations, STABILITY_SCALE.len() - 1)];
+        let mut target = soft.mul_f32(stability);
+        if dropped {
+            target = target.mul_f32(SCORE_DROP_SCALE);
+        }
+        // the next iteration usually takes as long as all previous ones
+        // combined
+        elapsed * 2 < min(target, self.limit.hard)
+    }
+}
+fn score_dropped(current_player: Color, previous: Score, score: Score) -> bool {
+    match (previous, score) {
+        (Score::Estimated(previous), Score::Estimated(score)) => {
+            let difference = score.centipawn() - previous.centipawn();
+            let difference = match current_player {
+                Color::White => difference,
+                Color::Black => -difference,
+            };
+            difference <= -SCORE_DROP
+        }
+        // a longer forced win is still a win, only losing it is a drop
+        (Score::Win(previous, _), Score::Win(score, _)) => {
+            previous == current_player && score != current_player
+        }
+        (Score::Win(winner, _), Score::Estimated(_)) => winner == current_player,
+        (Score::Estimated(_), Score::Win(winner, _)) => winner != current_player,
+    }
+}
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{
+        board::Lan,
+        color::Color,
+        heuristics::{Estimated, Score},
+        time_manager::{TimeLimit, TimeManager, score_dropped},
+    };
+
+    fn pawns(material: i16) -> Score {
+        Score::Estimated(Estimated {
+            material,
+            ..Estimated::default()
+        })
+    }
+
+    #[test]
+    fn limits_leave_time() {
+        let overhead = Duration::from_millis(50);
+        for (time, increment, moves_to_go) in [
+            (60_000, 0, 40.0),
+            (1_000, 1_000, 30.0),
+            (100, 2_000, 10.0),
+            (30, 0, 1.0),
+        ] {
+            let time = Duration::from_millis(time);
+            let limit = TimeLimit::from_clock(
+                time,
+                Duration::from_millis(increment),
+                moves_to_go,
+                overhead,
+            );
+            let soft = limit.soft.unwrap();
+            assert!(soft <= limit.hard);
+            assert!(limit.hard < time.saturating_sub(overhead) || limit.hard.is_zero());
+        }
+        let limit = TimeLimit::fixed(Duration::from_secs(1), overhead);
+        assert_eq!(limit.hard, Duration::from_millis(950));
+    }
+    #[test]
+    fn stable_best_move_stops_early() {
+        let limit = TimeLimit {
+            soft: Some(Duration::from_millis(100)),
+            hard: Duration::from_millis(400),
+        };
+        let elapsed = Duration::from_millis(40);
+        let moves: [Lan; 2] = ["e2e4".parse().unwrap(), "d2d4".parse().unwrap()];
+        let mut stable = TimeManager::new(limit);
+        let mut unstable = TimeManager::new(limit);
+        for i in 0..4 {
+            assert!(stable.should_continue_after(
+                elapsed,
+                Some(Color::White),
+                Some(moves[0]),
+                None
+            ));
+            assert!(unstable.should_continue_after(
+                elapsed,
+                Some(Color::White),
+                Some(moves[i % 2]),
+                None,
+            ));
+        }
+        assert!(!stable.should_continue_after(elapsed, Some(Color::White), Some(moves[0]), None));
+        assert!(unstable.should_continue_after(elapsed, Some(Color::White), Some(moves[0]), None));
+    }
+    #[test]
+    fn score_drop_extends() {
+        let limit = TimeLimit {
+            soft: Some(Duration::from_millis(100)),
+            hard: Duration::from_millis(400),
+        };
+        let movement: Lan = "e2e4".parse().unwrap();
+        for (color, score, expected) in [
+            (Color::White, 1, false),
+            (Color::White, 0, true),
+            (Color::Black, 2, true),
+        ] {
+            let mut time_manager = TimeManager::new(limit);
+            assert!(time_manager.should_continue_after(
+                Duration::from_millis(10),
+                Some(color),
+                Some(movement),
+                Some(pawns(1)),
+            ));
+            assert_eq!(
+                time_manager.should_continue_after(
+                    Duration::from_millis(80),
+                    Some(color),
+                    Some(movement),
+                    Some(pawns(score)),
+                ),
+                expected,
+            );
+        }
+    }
+    #[test]
+    fn longer_mate_is_not_a_drop() {
+        for (previous, score, expected) in [
+            (
+                Score::Win(Color::White, 3),
+                Score::Win(Color::White, 5),
+                false,
+            ),
+            (Score::Win(Color::White, 3), pawns(9), true),
+            (pawns(1), Score::Win(Color::White, 5), false),
+            (pawns(1), Score::Win(Color::Black, 5), true),
+        ] {
+            assert_eq!(
+                score_dropped(Color::White, previous, score),
+                expected,
+                "{previous:?} to {score:?}",
+            );
+        }
+    }
+    #[test]
+    fn hard_limit_is_never_passed() {
+        let mut fixed = TimeManager::new(TimeLimit::fixed(
+            Duration::from_millis(150),
+            Duration::from_millis(50),
+        ));
+        assert!(fixed.should_continue_after(Duration::from_millis(99), None, None, None));
+        assert!(!fixed.should_continue_after(Duration::from_millis(100), None, None, None));
+        let mut time_manager = TimeManager::new(TimeLimit {
+            soft: Some(Duration::from_secs(1)),
+            hard: Duration::from_millis(100),
+        });
+        assert!(!time_manager.should_continue_after(
+            Duration::from_millis(60),
+            Some(Color::White),
+            Some("e2e4".parse().unwrap()),
+            Some(pawns(1)),
+        ));
+    }
+}
@@ -3,13 +3,13 @@ use std::{
     fmt::Write as _,
     io::{BufRead, Write, stdin, stdout},
     num::NonZero,
+    time::Duration,
 };
 
 use crate::{
     board::{Board, Lan, NullableLan},
     color::Color,
     engine::{self, Engine},
-    game_tree::Table,
     misc::MEBIBYTES,
     uci::{
         input::{Go, Input},
@@ -23,7 +23,10 @@ mod output;
 const CHESS960: &str = "UCI_Chess960";
 const ENGINE_ABOUT: &str = "UCI_EngineAbout";
 
-const CONFIG: [Output; 9] = [
+// in milliseconds
+const DEFAULT_MOVE_OVERHEAD: u16 = 10;
+
+const CONFIG: [Output; 13] = [
     Output::Id {
         field: IdField::Name,
         value: concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")),
@@ -41,6 +44,15 @@ const CONFIG: [Output; 9] = [
             max: <i32>::MAX,
         }),
     },
+    Output::Option {
+        name: "MultiPV",
+        kind: OptionType::Spin,
+        default: Some(OptionValue::Int(1)),
+        boundary: Some(Boundary::Boundary {
+            min: 1,
+            max: <i32>::MAX,
+        }),
+    },
     Output::Option {
         name: "Hash",
         kind: OptionType::Spin,
@@ -50,12 +62,33 @@ const CONFIG: [Output; 9] = [
             max: <i32>::MAX,
         }),
     },
+    Output::Option {
+        name: "Move Overhead",
+        kind: OptionType::Spin,
+        default: Some(OptionValue::Int(DEFAULT_MOVE_OVERHEAD as i64)),
+        boundary: Some(Boundary::Boundary {
+            min: 0,
+            max: <i32>::MAX,
+        }),
+    },
     Output::Option {
         name: "Clear Hash",
         kind: OptionType::Button,
         default: None,
         boundary: None,
     },
+    Output::Option {
+        name: "Verify Hash",
+        kind: OptionType::Check,
+        default: Some(OptionValue::Bool(false)),
+        boundary: None,
+    },
+    Output::Option {
+        name: "Bounded Memory",
+        kind: OptionType::Check,
+        default: Some(OptionValue::Bool(false)),
+        boundary: None,
+    },
     Output::Option {
         name: CHESS960,
         kind: OptionType::Check,
@@ -76,6 +109,10 @@ const CONFIG: [Output; 9] = [
     },
     Output::UciOk,
 ];
+#[allow(
+    clippy::too_many_lines,
+    reason = "further decomposition could potentially hurt readability"
+)]
 pub fn uci_loop() {
     let mut output = stdout().lock();
     for config in CONFIG {
@@ -87,13 +124,13 @@ pub fn uci_loop() {
 
     let mut debug = false;
     let mut engine = LazyCell::new(Engine::new);
-    let mut hash_max_capacity = 0;
     let mut board = Board::starting_position();
     let mut move_count = 0;
     let mut new_game = true;
     let mut uci_new_game_available = false;
 
     let mut ponder = false;
+    let mut move_overhead = Duration::from_millis(DEFAULT_MOVE_OVERHEAD.into());
 
     let mut last_go = None;
     loop {
@@ -170,6 +207,27 @@ pub fn uci_loop() {
                         };
                         engine.set_thread(thread);
                     }
+                    "MultiPV" => {
+                        let Some(value) = value else {
+                            if debug {
+                                debug_print("set `MultiPV` without value; ignoring".to_string());
+                            }
+                            continue;
+                        };
+                        let multi_pv: NonZero<usize> = match value.parse() {
+                            Ok(multi_pv) => multi_pv,
+                            Err(err) => {
+                                if debug {
+                                    debug_print(
+                                        "set `MultiPV` to an invalid value; ignoring".to_string(),
+                                    );
+                                    debug_print(format!("error: {err}"));
+                                }
+                                continue;
+                            }
+                        };
+                        engine.set_multi_pv(multi_pv);
+                    }
                     "Hash" => {
                         let Some(value) = value else {
                             if debug {
@@ -189,8 +247,30 @@ pub fn uci_loop() {
                                 continue;
                             }
                         };
-                        hash_max_capacity = (size / Table::ELEMENT_SIZE).saturating_mul(MEBIBYTES);
-                        engine.set_hash_max_capacity(hash_max_capacity);
+                        engine.set_hash_size(size.saturating_mul(MEBIBYTES));
+                    }
+                    "Move Overhead" => {
+                        let Some(value) = value else {
+                            if debug {
+                                debug_print(
+                                    "set `Move Overhead` without value; ignoring".to_string(),
+                                );
+                            }
+                            continue;
+                        };
+                        move_overhead = match value.parse() {
+                            Ok(milliseconds) => Duration::from_millis(milliseconds),
+                            Err(err) => {
+                                if debug {
+                                    debug_print(
+                                        "set `Move Overhead` to an invalid value; ignoring"
+                                            .to_string(),
+                                    );
+                                    debug_print(format!("error: {err}"));
+                                }
+                                continue;
+                            }
+                        };
                     }
                     "Clear Hash" => {
                         if value.is_none() {
@@ -199,6 +279,46 @@ pub fn uci_loop() {
                             debug_print("set `Clear Hash` to invalid value; ignoring".to_string());
                         }
                     }
+                    "Verify Hash" => {
+                        if let Some(value) = value {
+                            let value = match value.parse() {
+                                Ok(value) => value,
+                                Err(err) => {
+                                    if debug {
+                                        debug_print(
+                                            "set `Verify Hash` to an invalid value; ignoring"
+                                                .to_string(),
+                                        );
+                                        debug_print(format!("error: {err}"));
+                                    }
+                                    continue;
+                                }
+                            };
+                            engine.set_hash_verification(value);
+                        } else if debug {
+                            debug_print("set `Verify Hash` without value; ignoring".to_string());
+                        }
+                    }
+                    "Bounded Memory" => {
+                        if let Some(value) = value {
+                            let value = match value.parse() {
+                                Ok(value) => value,
+                                Err(err) => {
+                                    if debug {
+                                        debug_print(
+                                            "set `Bounded Memory` to an invalid value; ignoring"
+                                                .to_string(),
+                                        );
+                                        debug_print(format!("error: {err}"));
+                                    }
+                                    continue;
+                                }
+                            };
+                            engine.set_bounded_memory(value);
+                        } else if debug {
+                            debug_print("set `Bounded Memory` without value; ignoring".to_string());
+                        }
+                    }
                     "Ponder" => {
                         if let Some(value) = value {
                             let value = match value.parse() {
@@ -248,10 +368,11 @@ pub fn uci_loop() {
                         debug_print("setting up new board".to_string());
                     }
                     board = position.board().unwrap();
+                    engine.set_board(board.clone());
                     for movement in &moves {
                         board.move_lan(*movement);
+                        engine.move_piece(*movement);
                     }
-                    engine.set_board(board.clone());
                     new_game = false;
                 } else {
                     let moves = &moves[move_count..];
@@ -269,6 +390,11 @@ pub fn uci_loop() {
                 }
                 move_count = moves.len();
             }
+            Input::Go(Go {
+                perft: Some(depth), ..
+            }) => {
+                engine.perft(board.clone(), depth, stdout());
+            }
             Input::Go(go) => {
                 new_game = false;
 
@@ -281,43 +407,33 @@ pub fn uci_loop() {
                     move_time: None,
                     ..go
                 });
-                let mate = go.mate.map(|moves| {
-                    let moves = moves.get();
-                    let plies = match board.current_player() {
-                        Color::White => moves * 2,
-                        Color::Black => moves * 2 - 1,
-                    };
-                    NonZero::new(plies).unwrap()
-                });
+                // the last move of the current player delivers the checkmate
+                let mate = go
+                    .mate
+                    .map(|moves| NonZero::new(moves.get() * 2 - 1).unwrap());
                 engine.calculate(
-                    go.estimate_move_time(&board),
+                    go.time_limit(&board, move_overhead),
                     go.depth,
                     go.nodes,
                     mate,
+                    go.search_moves,
                     go.ponder,
-                    info_callback(hash_max_capacity, board.current_player()),
+                    info_callback(board.current_player()),
                     best_move_callback(ponder),
                 );
-                if debug {
-                    if go.search_moves.is_some() {
-                        debug_print("`go searchmoves` is unsupported; ignoring".to_string());
-                    }
-                    if go.nodes.is_some() {
-                        debug_print("`go nodes` is unsupported; ignoring".to_string());
-                    }
-                }
             }
             Input::Stop => engine.stop(true),
             Input::PonderHit => {
                 engine.stop(false);
                 engine.move_piece(engine.ponder().unwrap());
                 engine.calculate(
-                    last_go.clone().unwrap().estimate_move_time(&board),
+                    last_go.clone().unwrap().time_limit(&board, move_overhead),
+                    None,
                     None,
                     None,
                     None,
                     false,
-                    info_callback(hash_max_capacity, board.current_player()),
+                    info_callback(board.current_player()),
                     best_move_callback(ponder),
                 );
             }
@@ -329,7 +445,7 @@ fn debug_print(message: String) {
     println!("{}", Output::Info(Info::Text(message.into_boxed_str())));
 }
 // TODO: don't print when it doesn't match the pondered move
-fn info_callback(hash_max_capacity: usize, current_player: Color) -> impl Fn(engine::Info) + Send {
+fn info_callback(current_player: Color) -> impl Fn(engine::Info) + Send {
     move |info| {
         // precision doesn't matter
         #[allow(
@@ -337,31 +453,27 @@ fn info_callback(hash_max_capacity: usize, current_player: Color) -> impl Fn(eng
             clippy::cast_sign_loss,
             clippy::cast_precision_loss
         )]
-        let hash_full = if info.hash_capacity >= hash_max_capacity {
-            1_000
-        } else {
-            (info.hash_capacity as f32 / hash_max_capacity as f32 * 1_000_f32) as u32
-        };
-        #[allow(
-            clippy::cast_possible_truncation,
-            clippy::cast_sign_loss,
-            clippy::cast_precision_loss
-        )]
         let nps = (info.nodes.get() as f32 / info.time.as_secs_f32()) as u32;
-        println!(
-            "{}",
-            Output::Info(Info::Search(SearchInfo {
-                depth: info.depth,
-                time: info.time,
-                nodes: info.nodes,
-                pv: info.pv,
-                score: info
-                    .score
-                    .map(|score| Score::from_centipawn(score.centipawn(), current_player,)),
-                hash_full,
-                nps
-            }))
-        );
+        let mut output = stdout().lock();
+        for (i, line) in info.lines.into_iter().enumerate() {
+            writeln!(
+                output,
+                "{}",
+                Output::Info(Info::Search(SearchInfo {
+                    depth: info.depth,
+                    multi_pv: NonZero::new(i + 1).unwrap(),
+                    time: info.time,
+                    nodes: info.nodes,
+                    pv: line.pv,
+                    score: line.score.map(|score| {
+                        Score::from_centipawn(score.centipawn(), line.bound, current_player)
+                    }),
+                    hash_full: info.hash_full,
+                    nps
+                }))
+            )
+            .unwrap();
+        }
     }
 }
 fn best_move_callback(ponder_enabled: bool) -> impl Fn(Option<Lan>, Option<Lan>) + Send {
@@ -15,6 +15,7 @@ use crate::{
     misc::{
         WithSpace, extract_prefix_token, split_by_token, starts_with_token, strip_prefix_token,
     },
+    time_manager::TimeLimit,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -139,7 +140,7 @@ impl Position {
     pub fn board(self) -> Result<Board, InvalidBoard> {
         match self {
             Position::StartPos => Ok(Board::starting_position()),
-            Position::Fen(fen) => fen.board.try_into(),
+            Position::Fen(fen) => fen.try_into(),
         }
     }
 }
@@ -188,11 +189,15 @@ pub struct Go {
     pub mate: Option<NonZero<u32>>,
     pub move_time: Option<Duration>,
     pub infinite: bool,
+
+    // Non-standard extension
+    pub perft: Option<u32>,
 }
 impl Go {
-    pub fn estimate_move_time(&self, board: &Board) -> Option<Duration> {
+    // None when there's no time limit
+    pub fn time_limit(&self, board: &Board, move_overhead: Duration) -> Option<TimeLimit> {
         if let Some(move_time) = self.move_time {
-            Some(move_time)
+            Some(TimeLimit::fixed(move_time, move_overhead))
         } else if self.infinite {
             None
         } else {
@@ -200,23 +205,20 @@ impl Go {
                 Color::White => (self.w_time, self.w_inc),
                 Color::Black => (self.b_time, self.b_inc),
             };
-            if let Some(time) = time {
-                let total_moves = board.estimate_moves_left();
-                let moves_to_go = if let Some(moves_to_go) = self.moves_to_go {
-                    #[allow(clippy::cast_precision_loss, reason = "we don't need the precision")]
-                    <f32>::min(moves_to_go.get() as f32, total_moves)
-                } else {
-                    total_moves
-                };
-                let estimated_time = time.div_f32(moves_to_go) + inc.unwrap_or_default();
-                if estimated_time > time {
-                    Some(time / 2)
-                } else {
-                    Some(estimated_time)
-                }
+            let time = time?;
+            let total_moves = board.estimate_moves_left();
+            let moves_to_go = if let Some(moves_to_go) = self.moves_to_go {
+                #[allow(clippy::cast_precision_loss, reason = "we don't need the precision")]
+                <f32>::min(moves_to_go.get() as f32, total_moves)
             } else {
-                None
-            }
+                total_moves
+            };
+            Some(TimeLimit::from_clock(
+                time,
+                inc.unwrap_or_default(),
+                moves_to_go,
+                move_overhead,
+            ))
         }
     }
 }
@@ -278,6 +280,10 @@ impl Display for Go {
             add_space(f)?;
             write!(f, "infinite")?;
         }
+        if let Some(perft) = self.perft {
+            add_space(f)?;
+            write!(f, "perft {perft}")?;
+        }
         Ok(())
     }
 }
@@ -331,6 +337,12 @@ impl FromStr for Go {
                     }
                 }
                 "infinite" => go.infinite = true,
+                "perft" => {
+                    let Some(depth) = tokens.next().and_then(|token| token.parse().ok()) else {
+                        continue;
+                    };
+                    go.perft = Some(depth);
+                }
                 _ => (),
             }
         }
@@ -404,7 +416,7 @@ impl Error for ParsePositionError {
 #[cfg(test)]
 mod test {
 
-    use crate::uci::input::{Input, Position};
+    use crate::uci::input::{Go, Input, Position};
 
     #[test]
     fn parse_position() {
@@ -417,4 +429,15 @@ mod test {
             }
         );
     }
+    #[test]
+    fn parse_go_perft() {
+        let input = Input::from_str("go perft 3").unwrap();
+        assert_eq!(
+            input,
+            Input::Go(Go {
+                perft: Some(3),
+                ..Go::default()
+            })
+        );
+    }
 }
@@ -7,6 +7,7 @@ use std::{
 use crate::{
     board::{Lan, NullableLan},
     color::Color,
+    game_tree::Bound,
     heuristics::Centipawn,
     misc::WithSpace,
 };
@@ -95,6 +96,7 @@ impl Display for Info {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SearchInfo {
     pub depth: NonZero<u32>,
+    pub multi_pv: NonZero<usize>,
     pub time: Duration,
     pub nodes: NonZero<u32>,
     pub pv: Box<[Lan]>,
@@ -106,8 +108,9 @@ impl Display for SearchInfo {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "depth {} time {} nodes {} pv {} hashfull {} nps {}",
+            "depth {} multipv {} time {} nodes {} pv {} hashfull {} nps {}",
             self.depth,
+            self.multi_pv,
             self.time.as_millis(),
             self.nodes,
             WithSpace(&self.pv),
@@ -126,7 +129,17 @@ pub struct Score {
     bound: Option<ScoreBound>,
 }
 impl Score {
-    pub fn from_centipawn(centipawn: Centipawn, current_player: Color) -> Self {
+    // The bound is relative to white like the centipawn
+    pub fn from_centipawn(centipawn: Centipawn, bound: Bound, current_player: Color) -> Self {
+        let bound = match (bound, current_player) {
+            (Bound::Exact, _) => None,
+            (Bound::Lower, Color::White) | (Bound::Upper, Color::Black) => {
+                Some(ScoreBound::LowerBound)
+            }
+            (Bound::Upper, Color::White) | (Bound::Lower, Color::Black) => {
+                Some(ScoreBound::UpperBound)
+            }
+        };
         match centipawn {
             Centipawn::Centipawn(centipawn) => {
                 let centipawn = match current_player {
@@ -135,20 +148,20 @@ impl Score {
                 };
                 Score {
                     score: ScoreValue::Cp(centipawn),
-                    bound: None,
+                    bound,
                 }
             }
-            Centipawn::Win(color) => {
-                if color == current_player {
-                    Score {
-                        score: ScoreValue::Mate(1),
-                        bound: Some(ScoreBound::LowerBound),
-                    }
+            Centipawn::Win(color, plies) => {
+                // UCI counts moves of the current player
+                #[allow(clippy::cast_possible_wrap, reason = "plies are small")]
+                let moves = if color == current_player {
+                    plies.div_ceil(2) as i32
                 } else {
-                    Score {
-                        score: ScoreValue::Mate(-1),
-                        bound: Some(ScoreBound::UpperBound),
-                    }
+                    -(plies.div_ceil(2) as i32)
+                };
+                Score {
+                    score: ScoreValue::Mate(moves),
+                    bound,
                 }
             }
         }
@@ -195,6 +208,7 @@ impl Display for ScoreBound {
 pub enum OptionType {
     Check,
     Spin,
+    #[allow(dead_code, reason = "part of the UCI protocol")]
     Combo,
     Button,
     String,
@@ -229,7 +243,11 @@ impl Display for OptionValue {
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Boundary {
-    Boundary { min: i32, max: i32 },
+    Boundary {
+        min: i32,
+        max: i32,
+    },
+    #[allow(dead_code, reason = "part of the UCI protocol")]
     Var(&'static [&'static str]),
 }
 impl Display for Boundary {
@@ -0,0 +1,52 @@
+use crate::{castling_right::CastlingRight, color::Color, coord::Coord, piece::ColoredPieceKind};
+
+const fn split_mix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+const fn keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut state = seed;
+    let mut i = 0;
+    while i < N {
+        let (new_state, key) = split_mix(state);
+        state = new_state;
+        keys[i] = key;
+        i += 1;
+    }
+    keys
+}
+
+// Indexed by `ColoredPieceKind` bits then by `Coord::index`
+static PIECES: [u64; 16 * 64] = keys(1);
+// Indexed by color then by file
+static CASTLING_RIGHTS: [u64; 2 * 8] = keys(2);
+static EN_PASSANT_FILES: [u64; 8] = keys(3);
+static BLACK_TO_MOVE: u64 = keys::<1>(4)[0];
+
+pub fn piece(piece: ColoredPieceKind, position: Coord) -> u64 {
+    let piece = ((piece.color() as usize) << 3) | piece.piece() as usize;
+    PIECES[piece * 64 + position.index() as usize]
+}
+pub fn castling_right(castling_right: CastlingRight) -> u64 {
+    [Color::White, Color::Black]
+        .into_iter()
+        .flat_map(|color| {
+            castling_right
+                .all(color)
+                .map(move |x| CASTLING_RIGHTS[color as usize * 8 + x as usize])
+        })
+        .fold(0, |key, item| key ^ item)
+}
+pub fn en_passant_target(en_passant_target: Option<Coord>) -> u64 {
+    en_passant_target.map_or(0, |position| EN_PASSANT_FILES[position.x() as usize])
+}
+pub fn current_player(color: Color) -> u64 {
+    match color {
+        Color::White => 0,
+        Color::Black => BLACK_TO_MOVE,
+    }
+}